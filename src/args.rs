@@ -0,0 +1,323 @@
+use anyhow::{bail, ensure, Context, Result};
+
+pub const DEFAULT_DBPATH: &str = "/var/lib/pacman/local";
+pub const PACMAN_CONF: &str = "/etc/pacman.conf";
+pub const DPKG_DBPATH: &str = "/var/lib/dpkg";
+
+// Prints to stdout instead of starting the interface.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Query {
+    // `name version` of every package like `pacman -Q`
+    List,
+    // names of dependencies no installed package requires like `pacman -Qdtq`
+    Orphans,
+    // all packages as a JSON array
+    Json,
+    // dependency graph
+    Graph(GraphFormat),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphFormat {
+    // Graphviz
+    Dot,
+    // for yEd and other graph editors
+    GraphMl,
+    // for Markdown documents
+    Mermaid,
+}
+
+// the package manager whose installed packages are shown
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backend {
+    #[default]
+    Pacman,
+    // Debian and derivatives like Ubuntu
+    Dpkg,
+}
+
+// background of the terminal, so that highlight colors stay readable
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub dbpath: String,
+    pub backend: Backend,
+    // how many entries a mouse wheel step moves
+    pub scroll_step: isize,
+    pub query: Option<Query>,
+    // restricts the DOT graph to this package and what it transitively depends on
+    pub root: Option<String>,
+    // only bold, underline and similar are used for emphasis
+    pub no_color: bool,
+    // every problem of the session is printed after quitting instead of only how many there were
+    pub verbose: bool,
+    // detected from the terminal if not given
+    pub theme: Option<Theme>,
+    // The main package is printed when quitting so that scripts can pick a package. The interface
+    // is drawn to stderr so that only the name goes to stdout.
+    pub print_selection: bool,
+}
+
+impl Args {
+    // Accepts the database path either as `--dbpath <path>`, `--dbpath=<path>` or as a single
+    // positional argument. Without it the default is looked up in `pacman_conf`.
+    pub fn parse(
+        mut args: impl Iterator<Item = String>,
+        pacman_conf: &std::path::Path,
+    ) -> Result<Self> {
+        let mut dbpath = None;
+        let mut scroll_step = 1;
+        let mut query = None;
+        let mut root = None;
+        let mut no_color = false;
+        let mut verbose = false;
+        let mut print_selection = false;
+        let mut theme = None;
+        let mut backend = Backend::default();
+        while let Some(arg) = args.next() {
+            let query_ = match arg.as_str() {
+                "--list" => Some(Query::List),
+                "--orphans" => Some(Query::Orphans),
+                "--json" => Some(Query::Json),
+                "--dot" => Some(Query::Graph(GraphFormat::Dot)),
+                _ => None,
+            };
+            let query_ = match (query_, flag_value("--export-format", &arg, &mut args)?) {
+                (None, Some(format)) => Some(Query::Graph(match format.as_str() {
+                    "dot" => GraphFormat::Dot,
+                    "graphml" => GraphFormat::GraphMl,
+                    "mermaid" => GraphFormat::Mermaid,
+                    _ => {
+                        bail!("invalid export format {format:?}, expected dot, graphml or mermaid")
+                    }
+                })),
+                (query_, _) => query_,
+            };
+            if let Some(query_) = query_ {
+                ensure!(
+                    query.is_none(),
+                    "only one of --list, --orphans, --json, --dot and --export-format can be given"
+                );
+                query = Some(query_);
+            } else if arg == "--no-color" {
+                no_color = true;
+            } else if arg == "--verbose" || arg == "-v" {
+                verbose = true;
+            } else if arg == "--print-selection" {
+                print_selection = true;
+            } else if let Some(theme_) = flag_value("--theme", &arg, &mut args)? {
+                theme = Some(match theme_.as_str() {
+                    "dark" => Theme::Dark,
+                    "light" => Theme::Light,
+                    _ => bail!("invalid theme {theme_:?}, expected dark or light"),
+                });
+            } else if let Some(backend_) = flag_value("--backend", &arg, &mut args)? {
+                backend = match backend_.as_str() {
+                    "pacman" => Backend::Pacman,
+                    "dpkg" => Backend::Dpkg,
+                    _ => bail!("unknown backend {backend_:?}, expected pacman or dpkg"),
+                };
+            } else if let Some(package) = flag_value("--root", &arg, &mut args)? {
+                root = Some(package);
+            } else if let Some(path) = flag_value("--dbpath", &arg, &mut args)? {
+                ensure!(dbpath.is_none(), "database path given more than once");
+                dbpath = Some(path);
+            } else if let Some(step) = flag_value("--scroll-step", &arg, &mut args)? {
+                scroll_step = step
+                    .parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .with_context(|| format!("invalid scroll step {step:?}"))?;
+            } else if arg.starts_with('-') {
+                bail!("unknown argument {arg:?}");
+            } else {
+                ensure!(dbpath.is_none(), "database path given more than once");
+                dbpath = Some(arg);
+            }
+        }
+        ensure!(
+            root.is_none() || matches!(query, Some(Query::Graph(_))),
+            "--root requires --dot or --export-format"
+        );
+        ensure!(
+            !print_selection || query.is_none(),
+            "--print-selection requires the interface"
+        );
+        Ok(Self {
+            dbpath: dbpath.unwrap_or_else(|| match backend {
+                Backend::Pacman => {
+                    pacman_dbpath(pacman_conf, 0).unwrap_or_else(|| DEFAULT_DBPATH.to_string())
+                }
+                Backend::Dpkg => DPKG_DBPATH.to_string(),
+            }),
+            backend,
+            scroll_step,
+            query,
+            root,
+            no_color,
+            verbose,
+            theme,
+            print_selection,
+        })
+    }
+}
+
+// Returns the value if `arg` is `flag` followed by a value as the next argument or `flag=value`.
+fn flag_value(
+    flag: &str,
+    arg: &str,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<Option<String>> {
+    if arg == flag {
+        return args
+            .next()
+            .with_context(|| format!("{flag} requires a value"))
+            .map(Some);
+    }
+    Ok(arg
+        .strip_prefix(flag)
+        .and_then(|value| value.strip_prefix('='))
+        .map(ToString::to_string))
+}
+
+// The local database of the `DBPath` set in the `[options]` section of a pacman.conf, following
+// `Include`s up to a few levels deep. `None` if the file can't be read or doesn't set it.
+fn pacman_dbpath(path: &std::path::Path, depth: usize) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let mut options = depth > 0;
+    let mut dbpath = None;
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(section) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            options = section == "options";
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) if options => (key.trim(), value.trim()),
+            _ => continue,
+        };
+        match key {
+            "DBPath" => dbpath = Some(std::path::Path::new(value).join("local")),
+            // includes of repositories usually only list mirrors and can't set the path
+            "Include" if depth < 4 => {
+                if let Some(path) = pacman_dbpath(std::path::Path::new(value), depth + 1) {
+                    dbpath = Some(path.into());
+                }
+            }
+            _ => (),
+        }
+    }
+    Some(dbpath?.to_str()?.to_string())
+}
+
+#[cfg(test)]
+fn parse(args: &[&str]) -> Result<Args> {
+    // not the pacman.conf of the system running the tests
+    Args::parse(
+        args.iter().map(ToString::to_string),
+        std::path::Path::new("/nonexistent/pacman.conf"),
+    )
+}
+
+#[test]
+fn parse_dbpath() {
+    assert_eq!(parse(&[]).unwrap().dbpath, DEFAULT_DBPATH);
+    assert_eq!(parse(&["a"]).unwrap().dbpath, "a");
+    assert_eq!(parse(&["--dbpath", "a"]).unwrap().dbpath, "a");
+    assert_eq!(parse(&["--dbpath=a"]).unwrap().dbpath, "a");
+    assert!(parse(&["--dbpath"]).is_err());
+    assert!(parse(&["a", "b"]).is_err());
+    assert!(parse(&["--unknown"]).is_err());
+}
+
+#[test]
+fn parse_pacman_conf() {
+    let dir = std::env::temp_dir().join(format!("leptohadron-conf-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let conf = dir.join("pacman.conf");
+    let include = dir.join("options.conf");
+    std::fs::write(&conf, "[options]\n#DBPath = /a\nHoldPkg = pacman\n").unwrap();
+    assert_eq!(pacman_dbpath(&conf, 0), None);
+    std::fs::write(
+        &conf,
+        "[options]\nDBPath = /a/ # moved\n[core]\nDBPath = /b\n",
+    )
+    .unwrap();
+    assert_eq!(pacman_dbpath(&conf, 0).as_deref(), Some("/a/local"));
+    std::fs::write(&include, "DBPath=/c\n").unwrap();
+    let contents = format!("[options]\nInclude = {}\n", include.display());
+    std::fs::write(&conf, contents).unwrap();
+    assert_eq!(pacman_dbpath(&conf, 0).as_deref(), Some("/c/local"));
+    // an include that includes itself stops after a few levels
+    std::fs::write(&include, format!("Include = {}\n", include.display())).unwrap();
+    assert_eq!(pacman_dbpath(&conf, 0), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(pacman_dbpath(&conf, 0), None);
+}
+
+#[test]
+fn parse_backend() {
+    assert_eq!(parse(&[]).unwrap().backend, Backend::Pacman);
+    assert_eq!(
+        parse(&["--backend=pacman"]).unwrap().backend,
+        Backend::Pacman
+    );
+    let args = parse(&["--backend", "dpkg"]).unwrap();
+    assert_eq!(args.backend, Backend::Dpkg);
+    assert_eq!(args.dbpath, DPKG_DBPATH);
+    assert_eq!(parse(&["--backend=dpkg", "a"]).unwrap().dbpath, "a");
+    assert!(parse(&["--backend", "rpm"]).is_err());
+}
+
+#[test]
+fn parse_scroll_step() {
+    assert_eq!(parse(&[]).unwrap().scroll_step, 1);
+    assert_eq!(parse(&["--scroll-step", "3"]).unwrap().scroll_step, 3);
+    assert_eq!(parse(&["--scroll-step=3"]).unwrap().scroll_step, 3);
+    assert!(parse(&["--scroll-step", "0"]).is_err());
+    assert!(parse(&["--scroll-step", "x"]).is_err());
+}
+
+#[test]
+fn parse_query() {
+    assert_eq!(parse(&[]).unwrap().query, None);
+    assert_eq!(parse(&["--orphans"]).unwrap().query, Some(Query::Orphans));
+    assert_eq!(parse(&["a", "--json"]).unwrap().query, Some(Query::Json));
+    assert!(parse(&["--list", "--json"]).is_err());
+    assert_eq!(
+        parse(&["--dot", "--root=a"]).unwrap().root.as_deref(),
+        Some("a")
+    );
+    assert!(parse(&["--root", "a"]).is_err());
+    let args = parse(&["--export-format", "mermaid", "--root", "a"]).unwrap();
+    assert_eq!(args.query, Some(Query::Graph(GraphFormat::Mermaid)));
+    assert_eq!(
+        parse(&["--export-format=graphml"]).unwrap().query,
+        Some(Query::Graph(GraphFormat::GraphMl))
+    );
+    assert!(parse(&["--export-format", "svg"]).is_err());
+    assert!(parse(&["--dot", "--export-format", "dot"]).is_err());
+}
+
+#[test]
+fn parse_colors() {
+    assert!(!parse(&[]).unwrap().no_color);
+    assert!(parse(&["--no-color"]).unwrap().no_color);
+    assert!(!parse(&[]).unwrap().verbose);
+    assert!(parse(&["-v"]).unwrap().verbose);
+    assert!(parse(&["--verbose"]).unwrap().verbose);
+    assert!(parse(&["--print-selection"]).unwrap().print_selection);
+    assert!(parse(&["--print-selection", "--list"]).is_err());
+    assert_eq!(parse(&[]).unwrap().theme, None);
+    assert_eq!(parse(&["--theme=light"]).unwrap().theme, Some(Theme::Light));
+    assert!(parse(&["--theme", "blue"]).is_err());
+}
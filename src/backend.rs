@@ -0,0 +1,59 @@
+use anyhow::Result;
+
+use crate::installed_packages::{self, PackageDesc};
+
+// Where installed packages come from. The interface only goes through this so that package
+// managers other than pacman can be added without changing it.
+pub trait PackageBackend {
+    // where the packages are read from, for messages
+    fn location(&self) -> &str;
+
+    // Every installed package in no particular order. Packages that fail to load are returned as
+    // errors so that the others can still be used.
+    fn packages(&self) -> Result<Box<dyn Iterator<Item = Result<PackageDesc>>>>;
+
+    // The paths the package installed.
+    fn files(&self, package: &PackageDesc) -> Result<Vec<String>>;
+
+    // When the packages last changed, so that they can be cached until then. `None` if unknown,
+    // which disables caching.
+    fn modified(&self) -> Option<std::time::SystemTime> {
+        None
+    }
+
+    // Whether the package manager is changing the packages right now, so that reading them would
+    // see a partial change. `false` if unknown.
+    fn locked(&self) -> bool {
+        false
+    }
+}
+
+// The local pacman database, a directory like `/var/lib/pacman/local`.
+pub struct Pacman {
+    pub dbpath: String,
+}
+
+impl PackageBackend for Pacman {
+    fn location(&self) -> &str {
+        self.dbpath.as_str()
+    }
+
+    fn packages(&self) -> Result<Box<dyn Iterator<Item = Result<PackageDesc>>>> {
+        Ok(Box::new(installed_packages::from_directory(&self.dbpath)?))
+    }
+
+    fn files(&self, package: &PackageDesc) -> Result<Vec<String>> {
+        installed_packages::files(&self.dbpath, package)
+    }
+
+    fn modified(&self) -> Option<std::time::SystemTime> {
+        installed_packages::modified(&self.dbpath).ok()
+    }
+
+    // Pacman creates a lock file next to the database for a transaction and removes it after.
+    fn locked(&self) -> bool {
+        std::path::Path::new(&self.dbpath)
+            .parent()
+            .is_some_and(|parent| parent.join("db.lck").exists())
+    }
+}
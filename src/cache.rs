@@ -0,0 +1,74 @@
+use std::{path::PathBuf, time::SystemTime};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use leptohadron::PackageDesc;
+
+// The parsed packages of a database so that starting doesn't parse every package again. Only valid
+// while the database hasn't been modified since. Saved with borrowed packages.
+#[derive(Deserialize, Serialize)]
+pub struct Cache<P = PackageDesc> {
+    location: String,
+    modified: SystemTime,
+    packages: Vec<P>,
+}
+
+impl Cache {
+    pub fn path() -> Option<PathBuf> {
+        crate::xdg::path("XDG_CACHE_HOME", ".cache", "packages.json")
+    }
+
+    // The cached packages if they are of the database at `location` as it was at `modified`. A
+    // missing or unreadable cache is the same as an outdated one because the database can always be
+    // parsed again.
+    pub fn load(
+        path: &std::path::Path,
+        location: &str,
+        modified: SystemTime,
+    ) -> Option<Vec<PackageDesc>> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        let cache: Cache = serde_json::from_str(&contents).ok()?;
+        (cache.location == location && cache.modified == modified).then_some(cache.packages)
+    }
+
+    pub fn save(
+        path: &std::path::Path,
+        location: &str,
+        modified: SystemTime,
+        packages: Vec<&PackageDesc>,
+    ) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context(format!("create {parent:?}"))?;
+        }
+        let cache = Cache {
+            location: location.to_string(),
+            modified,
+            packages,
+        };
+        let contents = serde_json::to_string(&cache).context("serialize")?;
+        std::fs::write(path, contents).context(format!("write {path:?}"))
+    }
+}
+
+#[test]
+fn cache_validity() {
+    let path = std::env::temp_dir().join(format!(
+        "leptohadron-cache-{}/packages.json",
+        std::process::id()
+    ));
+    let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1000);
+    assert!(Cache::load(&path, "db", modified).is_none());
+    let package = PackageDesc {
+        name: "a".to_string(),
+        version: "1".to_string(),
+        ..Default::default()
+    };
+    Cache::save(&path, "db", modified, vec![&package]).unwrap();
+    let packages = Cache::load(&path, "db", modified).unwrap();
+    assert_eq!(packages[0].name, "a");
+    assert!(Cache::load(&path, "other", modified).is_none());
+    let later = modified + std::time::Duration::from_secs(1);
+    assert!(Cache::load(&path, "db", later).is_none());
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
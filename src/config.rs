@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::keymap::Keymap;
+
+// Settings the user writes by hand, unlike `Preferences` which are saved when quitting. Missing
+// fields use the defaults.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    #[serde(rename = "keys")]
+    pub keymap: Keymap,
+    // quitting asks for confirmation first
+    pub confirm_quit: bool,
+    // entries PgUp and PgDown move, the height of the list if not set
+    pub page_size: Option<usize>,
+    // the install date is shown like `3 days ago` before the date
+    pub relative_dates: bool,
+    // sorting changes only the active list instead of all of them
+    pub per_column_sort: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            keymap: Default::default(),
+            confirm_quit: false,
+            page_size: None,
+            relative_dates: true,
+            per_column_sort: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        crate::xdg::path("XDG_CONFIG_HOME", ".config", "config.toml")
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        crate::xdg::load_toml(path, Self::parse)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut config: Self = toml::from_str(contents)?;
+        config.page_size = config.page_size.filter(|size| *size > 0);
+        Ok(config)
+    }
+}
+
+#[test]
+fn parse_config() {
+    assert_eq!(Config::parse("").unwrap(), Config::default());
+    let config = Config::parse("confirm-quit = true\npage-size = 5\n[keys]\nquit = \"Q\"").unwrap();
+    assert!(config.confirm_quit);
+    assert_eq!(config.page_size, Some(5));
+    assert!(config.relative_dates);
+    assert!(!config.per_column_sort);
+    assert!(
+        Config::parse("per-column-sort = true")
+            .unwrap()
+            .per_column_sort
+    );
+    assert!(
+        !Config::parse("relative-dates = false")
+            .unwrap()
+            .relative_dates
+    );
+    assert_eq!(
+        config.keymap.action(crossterm::event::KeyCode::Char('Q')),
+        Some(crate::keymap::Action::Quit)
+    );
+    assert!(Config::parse("[colors]").is_err());
+    assert!(Config::parse("confirm-quit = \"yes\"").is_err());
+}
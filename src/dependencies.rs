@@ -0,0 +1,76 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::installed_packages::{self, PackageDesc};
+
+// Maps virtual package names like `sh` to the installed packages providing them.
+pub fn providers(packages: &BTreeMap<String, PackageDesc>) -> BTreeMap<&str, BTreeSet<&str>> {
+    let mut providers: BTreeMap<&str, BTreeSet<&str>> = Default::default();
+    for (name, package) in packages.iter() {
+        for provide in package.provides.iter() {
+            let provide = installed_packages::dependency_name(provide);
+            providers.entry(provide).or_default().insert(name.as_str());
+        }
+    }
+    providers
+}
+
+// Maps packages to the installed packages that depend on them, optionally or not.
+pub fn dependants<'a>(
+    packages: &'a BTreeMap<String, PackageDesc>,
+    providers: &BTreeMap<&'a str, BTreeSet<&'a str>>,
+) -> BTreeMap<&'a str, BTreeSet<&'a str>> {
+    dependants_with(packages, providers, true)
+}
+
+// Like `dependants` but without optional dependencies, which pacman ignores when removing.
+pub fn required_dependants<'a>(
+    packages: &'a BTreeMap<String, PackageDesc>,
+    providers: &BTreeMap<&'a str, BTreeSet<&'a str>>,
+) -> BTreeMap<&'a str, BTreeSet<&'a str>> {
+    dependants_with(packages, providers, false)
+}
+
+fn dependants_with<'a>(
+    packages: &'a BTreeMap<String, PackageDesc>,
+    providers: &BTreeMap<&'a str, BTreeSet<&'a str>>,
+    optional: bool,
+) -> BTreeMap<&'a str, BTreeSet<&'a str>> {
+    let mut dependants: BTreeMap<&str, BTreeSet<&str>> = Default::default();
+    for (name, package) in packages.iter() {
+        let optional_dependencies = match optional {
+            true => package.optional_dependencies.as_slice(),
+            false => &[],
+        };
+        for dep in package
+            .dependencies
+            .iter()
+            .map(|dep| dep.name.as_str())
+            .chain(optional_dependencies.iter().map(|dep| dep.name.as_str()))
+        {
+            // dependencies that aren't installed resolve to nothing
+            for dep in resolve_dependency(packages, providers, dep) {
+                dependants.entry(dep).or_default().insert(name.as_str());
+            }
+        }
+    }
+    dependants
+}
+
+// Returns the installed packages satisfying a dependency. A package with the dependency's name takes
+// precedence over packages that merely provide it. Version constraints are ignored.
+pub fn resolve_dependency<'a>(
+    packages: &'a BTreeMap<String, PackageDesc>,
+    providers: &BTreeMap<&'a str, BTreeSet<&'a str>>,
+    dependency: &str,
+) -> Vec<&'a str> {
+    let dependency = installed_packages::dependency_name(dependency);
+    match packages.get_key_value(dependency) {
+        Some((name, _)) => vec![name.as_str()],
+        None => providers
+            .get(dependency)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect(),
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    backend::PackageBackend,
+    installed_packages::{Dependency, OptionalDependency, PackageDesc, Reason},
+};
+
+// The dpkg database of Debian and Ubuntu, a directory like `/var/lib/dpkg`. Which packages were
+// installed automatically is read from apt's `extended_states` next to it.
+pub struct Dpkg {
+    pub dbpath: String,
+}
+
+impl PackageBackend for Dpkg {
+    fn location(&self) -> &str {
+        self.dbpath.as_str()
+    }
+
+    fn packages(&self) -> Result<Box<dyn Iterator<Item = Result<PackageDesc>>>> {
+        let path = std::path::Path::new(&self.dbpath).join("status");
+        let status = std::fs::read_to_string(&path).context(format!("read {path:?}"))?;
+        let path = std::path::Path::new(&self.dbpath).join("../apt/extended_states");
+        let extended_states = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            // without apt everything counts as explicitly installed
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err).context(format!("read {path:?}")),
+        };
+        let auto = auto_installed(&extended_states);
+        Ok(Box::new(parse_status(&status, &auto).into_iter()))
+    }
+
+    fn files(&self, package: &PackageDesc) -> Result<Vec<String>> {
+        let info = std::path::Path::new(&self.dbpath).join("info");
+        // Packages that can be installed for several architectures include it in the name, and
+        // they can be installed for several at once, see `merge_architectures`.
+        let path = info.join(format!("{}.list", package.name));
+        let paths = match (path.exists(), &package.arch) {
+            (false, Some(arch)) => arch
+                .split_whitespace()
+                .map(|arch| info.join(format!("{}:{arch}.list", package.name)))
+                .collect(),
+            _ => vec![path],
+        };
+        let several = paths.len() > 1;
+        let mut files = Vec::new();
+        for path in paths {
+            let contents = std::fs::read_to_string(&path).context(format!("read {path:?}"))?;
+            files.extend(
+                contents
+                    .lines()
+                    .filter(|line| !line.is_empty() && *line != "/.")
+                    .map(|line| line.trim_start_matches('/').to_string()),
+            );
+        }
+        // architectures share files like the documentation
+        if several {
+            files.sort();
+            files.dedup();
+        }
+        Ok(files)
+    }
+
+    // Both files are rewritten as a whole when packages change.
+    fn modified(&self) -> Option<std::time::SystemTime> {
+        let modified = |path: &str| {
+            let path = std::path::Path::new(&self.dbpath).join(path);
+            std::fs::metadata(path).and_then(|metadata| metadata.modified())
+        };
+        let status = modified("status").ok()?;
+        Some(match modified("../apt/extended_states") {
+            Ok(extended_states) => status.max(extended_states),
+            Err(_) => status,
+        })
+    }
+
+    // Unlike pacman's, the lock file always exists and is locked with `fcntl` while in use. It is
+    // only readable by root on most systems, which makes this unknown for other users.
+    fn locked(&self) -> bool {
+        let path = std::path::Path::new(&self.dbpath).join("lock-frontend");
+        match std::fs::File::open(path) {
+            Ok(file) => fcntl_locked(&file),
+            Err(_) => false,
+        }
+    }
+}
+
+// Whether another process holds a `fcntl` lock on the file.
+fn fcntl_locked(file: &std::fs::File) -> bool {
+    use std::os::fd::AsRawFd;
+    // Unsafe because of FFI. An all zero `flock` is valid and it outlives the call.
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    let result = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLK, &mut lock) };
+    result == 0 && lock.l_type != libc::F_UNLCK as libc::c_short
+}
+
+// Splits a file of `Field: value` paragraphs separated by empty lines. Lines starting with a space
+// continue the previous field.
+fn paragraphs(contents: &str) -> Vec<Vec<(&str, String)>> {
+    let mut paragraphs = Vec::new();
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    for line in contents.lines() {
+        if line.trim().is_empty() {
+            if !fields.is_empty() {
+                paragraphs.push(std::mem::take(&mut fields));
+            }
+        } else if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = fields.last_mut() {
+                value.push('\n');
+                value.push_str(line.trim());
+            }
+        } else if let Some((field, value)) = line.split_once(':') {
+            fields.push((field, value.trim().to_string()));
+        }
+    }
+    if !fields.is_empty() {
+        paragraphs.push(fields);
+    }
+    paragraphs
+}
+
+// Names of the packages apt marked as automatically installed.
+fn auto_installed(extended_states: &str) -> BTreeSet<String> {
+    paragraphs(extended_states)
+        .into_iter()
+        .filter(|fields| fields.contains(&("Auto-Installed", "1".to_string())))
+        .filter_map(|fields| {
+            let (_, name) = fields.into_iter().find(|(field, _)| *field == "Package")?;
+            Some(name)
+        })
+        .collect()
+}
+
+// Returns the installed packages. Packages that are only known, like removed ones whose
+// configuration files remain, are left out.
+pub fn parse_status(status: &str, auto_installed: &BTreeSet<String>) -> Vec<Result<PackageDesc>> {
+    let packages = paragraphs(status)
+        .into_iter()
+        .filter(|fields| {
+            fields.iter().any(|(field, value)| {
+                *field == "Status" && value.split_whitespace().last() == Some("installed")
+            })
+        })
+        .map(|fields| {
+            let mut desc = PackageDesc::default();
+            for (field, value) in fields {
+                match field {
+                    "Package" => desc.name = value,
+                    "Version" => desc.version = value,
+                    // the first line is the summary
+                    "Description" => desc.description = value.lines().next().map(str::to_string),
+                    "Homepage" => desc.url = Some(value),
+                    "Maintainer" => desc.packager = Some(value),
+                    "Architecture" => desc.arch = Some(value),
+                    "Installed-Size" => {
+                        let kib: u64 = value
+                            .parse()
+                            .with_context(|| format!("invalid Installed-Size {value:?}"))?;
+                        let bytes = kib
+                            .checked_mul(1024)
+                            .with_context(|| format!("Installed-Size {value:?} too large"))?;
+                        desc.size = Some(bytes);
+                    }
+                    "Depends" | "Pre-Depends" => desc
+                        .dependencies
+                        .extend(relations(&value).map(|relation| Dependency::parse(&relation))),
+                    "Recommends" | "Suggests" => {
+                        desc.optional_dependencies
+                            .extend(relations(&value).map(|name| OptionalDependency {
+                                name,
+                                description: None,
+                            }))
+                    }
+                    "Provides" => desc.provides.extend(relations(&value)),
+                    "Conflicts" | "Breaks" => desc.conflicts.extend(relations(&value)),
+                    "Replaces" => desc.replaces.extend(relations(&value)),
+                    "Section" => desc.groups.push(value),
+                    _ => (),
+                }
+            }
+            anyhow::ensure!(!desc.name.is_empty(), "paragraph without Package");
+            if auto_installed.contains(&desc.name) {
+                desc.reason = Reason::Dependency;
+            }
+            Ok(desc)
+        })
+        .collect();
+    merge_architectures(packages)
+}
+
+// Multiarch lets a package like `libc6` be installed for several architectures, each with its own
+// paragraph. Dependencies refer to it by name only, so paragraphs with the same name and version
+// become one package with all of the architectures and their sizes added up.
+fn merge_architectures(packages: Vec<Result<PackageDesc>>) -> Vec<Result<PackageDesc>> {
+    let mut merged: Vec<Result<PackageDesc>> = Vec::new();
+    // name -> index in `merged`
+    let mut indices: BTreeMap<String, usize> = BTreeMap::new();
+    for package in packages {
+        let other = match package {
+            Ok(desc) => desc,
+            Err(err) => {
+                merged.push(Err(err));
+                continue;
+            }
+        };
+        let desc = match indices.get(&other.name).map(|i| &mut merged[*i]) {
+            Some(Ok(desc)) if desc.version == other.version => desc,
+            _ => {
+                indices.insert(other.name.clone(), merged.len());
+                merged.push(Ok(other));
+                continue;
+            }
+        };
+        desc.arch = match (desc.arch.take(), other.arch) {
+            (Some(arch), Some(other)) => Some(format!("{arch} {other}")),
+            (arch, other) => arch.or(other),
+        };
+        desc.size = match (desc.size, other.size) {
+            (Some(size), Some(other)) => Some(size.saturating_add(other)),
+            (size, other) => size.or(other),
+        };
+        for dependency in other.dependencies {
+            if !desc.dependencies.contains(&dependency) {
+                desc.dependencies.push(dependency);
+            }
+        }
+        for dependency in other.optional_dependencies {
+            if !desc
+                .optional_dependencies
+                .iter()
+                .any(|existing| existing.name == dependency.name)
+            {
+                desc.optional_dependencies.push(dependency);
+            }
+        }
+        for (names, other) in [
+            (&mut desc.provides, other.provides),
+            (&mut desc.conflicts, other.conflicts),
+            (&mut desc.replaces, other.replaces),
+            (&mut desc.groups, other.groups),
+        ] {
+            for name in other {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+    merged
+}
+
+// Turns a list like `libc6 (>= 2.34), default-mta | mail-transport-agent` into entries like
+// `libc6>=2.34` as pacman writes them. Only the first of alternatives is kept.
+fn relations(value: &str) -> impl Iterator<Item = String> + '_ {
+    value.split(',').filter_map(|relation| {
+        let relation = relation.split('|').next()?.trim();
+        let (name, version) = match relation.split_once('(') {
+            Some((name, version)) => (name.trim(), version.trim_end_matches(')').trim()),
+            None => (relation, ""),
+        };
+        // architecture qualifiers like `python3:any`
+        let name = name.split(':').next()?;
+        if name.is_empty() {
+            return None;
+        }
+        Some(format!("{name}{}", version.replace(' ', "")))
+    })
+}
+
+#[test]
+fn parse_dpkg_status() {
+    let status = "\
+Package: curl
+Status: install ok installed
+Installed-Size: 500
+Architecture: amd64
+Version: 8.5.0-2
+Depends: libc6 (>= 2.34), libcurl4t64 (= 8.5.0-2), zlib1g | zlib-ng
+Recommends: ca-certificates
+Description: command line tool for transferring data
+ curl is a client to get files from servers.
+
+Package: old
+Status: deinstall ok config-files
+Version: 1
+
+Package: libc6
+Status: install ok installed
+Version: 2.39
+Installed-Size: 1
+";
+    let auto = auto_installed("Package: libc6\nArchitecture: amd64\nAuto-Installed: 1\n");
+    let packages: Vec<PackageDesc> = parse_status(status, &auto)
+        .into_iter()
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(packages.len(), 2);
+    let curl = &packages[0];
+    assert_eq!(curl.name, "curl");
+    assert_eq!(curl.size, Some(500 * 1024));
+    let dependencies: Vec<Dependency> = ["libc6>=2.34", "libcurl4t64=8.5.0-2", "zlib1g"]
+        .into_iter()
+        .map(Dependency::parse)
+        .collect();
+    assert_eq!(curl.dependencies, dependencies);
+    assert_eq!(curl.optional_dependencies[0].name, "ca-certificates");
+    assert_eq!(
+        curl.description.as_deref(),
+        Some("command line tool for transferring data")
+    );
+    assert!(matches!(curl.reason, Reason::Explicit));
+    assert!(matches!(packages[1].reason, Reason::Dependency));
+}
+
+#[test]
+fn parse_dpkg_multiarch() {
+    let status = "\
+Package: libc6
+Status: install ok installed
+Installed-Size: 2
+Architecture: amd64
+Version: 2.39
+Depends: libgcc-s1
+
+Package: libc6
+Status: install ok installed
+Installed-Size: 3
+Architecture: i386
+Version: 2.39
+Depends: libgcc-s1, libcrypt1
+";
+    let packages: Vec<PackageDesc> = parse_status(status, &BTreeSet::new())
+        .into_iter()
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(packages.len(), 1);
+    let libc = &packages[0];
+    assert_eq!(libc.arch.as_deref(), Some("amd64 i386"));
+    assert_eq!(libc.size, Some(5 * 1024));
+    let names: Vec<&str> = libc.dependencies.iter().map(|d| d.name.as_str()).collect();
+    assert_eq!(names, ["libgcc-s1", "libcrypt1"]);
+}
+
+#[test]
+fn parse_dpkg_size_overflow() {
+    let status = format!(
+        "Package: big\nStatus: install ok installed\nInstalled-Size: {}\n",
+        u64::MAX / 1024 + 1
+    );
+    let packages = parse_status(&status, &BTreeSet::new());
+    assert!(packages[0].is_err());
+}
+
+#[test]
+fn dpkg_lock_needs_to_be_held() {
+    let dbpath = std::env::temp_dir().join(format!("leptohadron-dpkg-{}", std::process::id()));
+    std::fs::create_dir_all(&dbpath).unwrap();
+    let dpkg = Dpkg {
+        dbpath: dbpath.to_str().unwrap().to_string(),
+    };
+    assert!(!dpkg.locked());
+    std::fs::write(dbpath.join("lock-frontend"), "").unwrap();
+    assert!(!dpkg.locked());
+    std::fs::remove_dir_all(&dbpath).unwrap();
+}
+
+#[test]
+fn parse_dpkg_relations() {
+    let relations: Vec<String> = relations("python3:any (>= 3.8), , perl").collect();
+    assert_eq!(relations, ["python3>=3.8", "perl"]);
+}
@@ -1,8 +1,21 @@
 use anyhow::{bail, ensure, Context, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
+// Reads and parses the packages in parallel. They are returned in directory order.
 pub fn from_directory(path: &str) -> Result<impl Iterator<Item = Result<PackageDesc>>> {
-    let iter = std::fs::read_dir(path).context("read_dir")?;
-    Ok(iter
+    let entries: Vec<_> = match std::fs::read_dir(path) {
+        Ok(entries) => entries.collect(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => bail!(
+            "database directory {path} not found: run with --dbpath or check that pacman is installed"
+        ),
+        Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+            bail!("no permission to read database directory {path}")
+        }
+        Err(err) => return Err(err).context("read_dir"),
+    };
+    let packages: Vec<Result<Option<PackageDesc>>> = entries
+        .into_par_iter()
         .map(|entry| {
             let entry = entry.context("entry")?;
             if !entry.file_type().context("file_type")?.is_dir() {
@@ -10,34 +23,200 @@ pub fn from_directory(path: &str) -> Result<impl Iterator<Item = Result<PackageD
             }
             let mut path = entry.path();
             path.push("desc");
-            let contents = std::fs::read_to_string(&path).context(format!("read {:?}", path))?;
+            let contents = match std::fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                // archived databases can have compressed files
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    path.set_extension("gz");
+                    match path.exists() {
+                        true => read_gzip(&path)?,
+                        // not a package
+                        false => return Ok(None),
+                    }
+                }
+                Err(err) => return Err(err).context(format!("read {:?}", path)),
+            };
             let desc =
                 PackageDesc::parse(contents.as_str()).context(format!("parse {:?}", path))?;
             Ok(Some(desc))
         })
-        .filter_map(Result::transpose))
+        .collect();
+    Ok(packages.into_iter().filter_map(Result::transpose))
+}
+
+fn read_gzip(path: &std::path::Path) -> Result<String> {
+    use std::io::Read;
+    let file = std::fs::File::open(path).context(format!("open {path:?}"))?;
+    let mut contents = String::new();
+    flate2::read::GzDecoder::new(file)
+        .read_to_string(&mut contents)
+        .context(format!("decompress {path:?}"))?;
+    Ok(contents)
+}
+
+#[test]
+fn from_directory_skips_non_packages() {
+    let path = std::env::temp_dir().join(format!("leptohadron-test-{}", std::process::id()));
+    std::fs::create_dir_all(path.join("empty")).unwrap();
+    std::fs::create_dir_all(path.join("package")).unwrap();
+    std::fs::write(
+        path.join("package/desc"),
+        "%NAME%\npackage\n\n%VERSION%\n1\n\n",
+    )
+    .unwrap();
+    std::fs::write(path.join("ALPM_DB_VERSION"), "9\n").unwrap();
+    let packages: Vec<PackageDesc> = from_directory(path.to_str().unwrap())
+        .unwrap()
+        .collect::<Result<_>>()
+        .unwrap();
+    std::fs::remove_dir_all(&path).unwrap();
+    assert_eq!(packages.len(), 1);
+    assert_eq!(packages[0].name, "package");
+}
+
+// The latest modification time of the directory and the `desc` files in it. Installing or removing
+// packages changes the directory itself, but changing the install reason only rewrites a
+// `desc` file.
+pub fn modified(path: &str) -> Result<std::time::SystemTime> {
+    let mut modified = std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .context(format!("metadata {path}"))?;
+    for entry in std::fs::read_dir(path).context("read_dir")? {
+        let entry = entry.context("entry")?.path();
+        for desc in [entry.join("desc"), entry.join("desc.gz")] {
+            // not a package
+            if let Ok(metadata) = std::fs::metadata(&desc) {
+                modified = modified.max(metadata.modified().context(format!("modified {desc:?}"))?);
+            }
+        }
+    }
+    Ok(modified)
+}
+
+#[test]
+fn from_directory_missing() {
+    let err = from_directory("/nonexistent/leptohadron").err().unwrap();
+    assert!(err.to_string().contains("not found: run with --dbpath"));
 }
 
-#[derive(Debug, Default)]
+// Reads the paths the package installed from the `files` file next to its `desc` in the database
+// directory `path`. Separate from parsing `desc` because the file lists are large.
+pub fn files(path: &str, package: &PackageDesc) -> Result<Vec<String>> {
+    let path = std::path::Path::new(path)
+        .join(format!("{}-{}", package.name, package.version))
+        .join("files");
+    let contents = std::fs::read_to_string(&path).context(format!("read {:?}", path))?;
+    Ok(parse_files(contents.as_str()))
+}
+
+// Parses the `%FILES%` section of a `files` file. Paths are relative to the root and directories
+// end with `/`.
+pub fn parse_files(s: &str) -> Vec<String> {
+    s.split_terminator("\n\n")
+        .filter_map(|section| section.strip_prefix("%FILES%\n"))
+        .flat_map(|section| section.split_terminator('\n'))
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[test]
+fn parse_files_section() {
+    let files =
+        parse_files("%FILES%\nusr/\nusr/bin/\nusr/bin/foo\n\n%BACKUP%\netc/foo.conf\tabc\n\n");
+    assert_eq!(files, ["usr/", "usr/bin/", "usr/bin/foo"]);
+    assert!(parse_files("").is_empty());
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct PackageDesc {
     pub name: String,
     pub version: String,
-    pub description: String,
-    pub url: String,
+    // Missing for some locally built packages.
+    pub description: Option<String>,
+    // Missing for some locally built packages.
+    pub url: Option<String>,
+    // For example `Jane Doe <jane@archlinux.org>`.
+    pub packager: Option<String>,
+    // For example `x86_64` or `any`. Not validated because new architectures appear over time.
+    pub arch: Option<String>,
     pub reason: Reason,
     pub size: Option<u64>,
-    pub dependencies: Vec<String>,
+    // Unix timestamp
+    pub install_date: Option<i64>,
+    // Unix timestamp
+    pub build_date: Option<i64>,
+    pub dependencies: Vec<Dependency>,
     pub optional_dependencies: Vec<OptionalDependency>,
+    // Virtual package names this package satisfies. Entries may carry a version like `foo=1.2`.
+    pub provides: Vec<String>,
+    // Entries may carry a version constraint like `foo<2.0`.
+    pub conflicts: Vec<String>,
+    // Packages this package replaces, for example because it was renamed.
+    pub replaces: Vec<String>,
+    // Package groups like `base-devel` or `gnome`.
+    pub groups: Vec<String>,
+    pub license: Vec<String>,
+    // Problems that didn't prevent parsing, like unknown values.
+    #[serde(skip)]
+    pub warnings: Vec<String>,
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Reason {
     #[default]
     Explicit,
     Dependency,
 }
 
-#[derive(Debug, Default)]
+// Returns the package name of a dependency string by removing the version constraint. For example
+// `glibc>=2.38` becomes `glibc`.
+pub fn dependency_name(dependency: &str) -> &str {
+    // Unwrap because split always has at least one item.
+    dependency.split(['<', '>', '=']).next().unwrap()
+}
+
+#[test]
+fn dependency_name_strips_constraint() {
+    assert_eq!(dependency_name("glibc"), "glibc");
+    assert_eq!(dependency_name("glibc>=2.38"), "glibc");
+    assert_eq!(dependency_name("python<3.13"), "python");
+    assert_eq!(dependency_name("foo=1.2"), "foo");
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Dependency {
+    // package or virtual package name
+    pub name: String,
+    // For example `>=2.38`, including the operator.
+    pub constraint: Option<String>,
+}
+
+impl Dependency {
+    // from an entry like `glibc>=2.38`
+    pub fn parse(dependency: &str) -> Self {
+        let name = dependency_name(dependency);
+        let constraint = &dependency[name.len()..];
+        Self {
+            name: name.to_string(),
+            constraint: (!constraint.is_empty()).then(|| constraint.to_string()),
+        }
+    }
+}
+
+#[test]
+fn parse_dependency() {
+    let dependency = Dependency::parse("glibc>=2.38");
+    assert_eq!(dependency.name, "glibc");
+    assert_eq!(dependency.constraint.as_deref(), Some(">=2.38"));
+    assert_eq!(Dependency::parse("sh").constraint, None);
+    assert_eq!(
+        Dependency::parse("foo=1.2").constraint.as_deref(),
+        Some("=1.2")
+    );
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct OptionalDependency {
     pub name: String,
     pub description: Option<String>,
@@ -46,7 +225,8 @@ pub struct OptionalDependency {
 impl OptionalDependency {
     // without ending newline
     fn parse(line: &str) -> Self {
-        let mut split = line.split(": ").map(ToString::to_string);
+        // The description can contain ": " too.
+        let mut split = line.splitn(2, ": ").map(ToString::to_string);
         // Unwrap because split always has at least one item.
         let name = split.next().unwrap();
         let description = split.next();
@@ -68,8 +248,16 @@ fn parse_optional_dependency_with_reason() {
     assert_eq!(a.description, Some("reason".to_string()));
 }
 
+#[test]
+fn parse_optional_dependency_with_colon_in_reason() {
+    let a = OptionalDependency::parse("gst-plugins: for playback: needed");
+    assert_eq!(a.name, "gst-plugins");
+    assert_eq!(a.description, Some("for playback: needed".to_string()));
+}
+
 impl PackageDesc {
-    fn parse(s: &str) -> Result<Self> {
+    // Parses the contents of a `desc` file.
+    pub fn parse(s: &str) -> Result<Self> {
         let mut self_ = Self::default();
         for section in s.split_terminator("\n\n") {
             let mut lines = section.split_terminator('\n');
@@ -83,15 +271,27 @@ impl PackageDesc {
                     self_.version = first_body.to_string();
                 }
                 "%DESC%" => {
-                    self_.description = first_body.to_string();
+                    self_.description = Some(first_body.to_string());
                 }
                 "%URL%" => {
-                    self_.url = first_body.to_string();
+                    self_.url = Some(first_body.to_string());
+                }
+                "%PACKAGER%" => {
+                    self_.packager = Some(first_body.to_string());
+                }
+                "%ARCH%" => {
+                    self_.arch = Some(first_body.to_string());
                 }
                 "%REASON%" => {
                     self_.reason = match first_body {
+                        "0" => Reason::Explicit,
                         "1" => Reason::Dependency,
-                        _ => bail!("unexpected reason {first_body:?}"),
+                        _ => {
+                            self_.warnings.push(format!(
+                                "unexpected reason {first_body:?}, assuming explicit"
+                            ));
+                            Reason::Explicit
+                        }
                     }
                 }
                 "%SIZE%" => {
@@ -101,9 +301,23 @@ impl PackageDesc {
                             .context(format!("parse size {first_body:?}"))?,
                     );
                 }
+                "%INSTALLDATE%" => {
+                    self_.install_date = Some(
+                        first_body
+                            .parse()
+                            .context(format!("parse install date {first_body:?}"))?,
+                    );
+                }
+                "%BUILDDATE%" => {
+                    self_.build_date = Some(
+                        first_body
+                            .parse()
+                            .context(format!("parse build date {first_body:?}"))?,
+                    );
+                }
                 "%DEPENDS%" => {
-                    self_.dependencies.push(first_body.to_string());
-                    self_.dependencies.extend(lines.map(ToString::to_string));
+                    self_.dependencies.push(Dependency::parse(first_body));
+                    self_.dependencies.extend(lines.map(Dependency::parse));
                 }
                 "%OPTDEPENDS%" => {
                     self_
@@ -113,17 +327,326 @@ impl PackageDesc {
                         .optional_dependencies
                         .extend(lines.map(OptionalDependency::parse));
                 }
+                "%PROVIDES%" => {
+                    self_.provides.push(first_body.to_string());
+                    self_.provides.extend(lines.map(ToString::to_string));
+                }
+                "%CONFLICTS%" => {
+                    self_.conflicts.push(first_body.to_string());
+                    self_.conflicts.extend(lines.map(ToString::to_string));
+                }
+                "%REPLACES%" => {
+                    self_.replaces.push(first_body.to_string());
+                    self_.replaces.extend(lines.map(ToString::to_string));
+                }
+                "%GROUPS%" => {
+                    self_.groups.push(first_body.to_string());
+                    self_.groups.extend(lines.map(ToString::to_string));
+                }
+                "%LICENSE%" => {
+                    self_.license.push(first_body.to_string());
+                    self_.license.extend(lines.map(ToString::to_string));
+                }
                 _ => (),
             }
         }
         ensure!(!self_.name.is_empty());
         ensure!(!self_.version.is_empty());
-        ensure!(!self_.description.is_empty());
-        ensure!(!self_.url.is_empty());
         Ok(self_)
     }
 }
 
+// Parses a desc file consisting of the required sections followed by `sections`.
+#[cfg(test)]
+fn parse_with_sections(sections: &str) -> PackageDesc {
+    let desc = format!("%NAME%\nname\n\n%VERSION%\n1\n\n%DESC%\nd\n\n%URL%\nu\n\n{sections}");
+    PackageDesc::parse(desc.as_str()).unwrap()
+}
+
+#[test]
+fn parse_without_url() {
+    let desc = PackageDesc::parse("%NAME%\nname\n\n%VERSION%\n1\n\n%DESC%\nd\n\n").unwrap();
+    assert_eq!(desc.url, None);
+    assert_eq!(parse_with_sections("").url.as_deref(), Some("u"));
+}
+
+#[test]
+fn parse_without_description() {
+    let desc = PackageDesc::parse("%NAME%\nname\n\n%VERSION%\n1\n\n%URL%\nu\n\n").unwrap();
+    assert_eq!(desc.description, None);
+    assert_eq!(parse_with_sections("").description.as_deref(), Some("d"));
+}
+
+#[test]
+fn parse_unexpected_reason() {
+    let desc = parse_with_sections("%REASON%\n2\n\n");
+    assert!(matches!(desc.reason, Reason::Explicit));
+    assert_eq!(desc.warnings.len(), 1);
+    assert!(parse_with_sections("%REASON%\n1\n\n").warnings.is_empty());
+}
+
+#[test]
+fn serialize_reason() {
+    assert_eq!(
+        serde_json::to_string(&Reason::Explicit).unwrap(),
+        "\"explicit\""
+    );
+    assert_eq!(
+        serde_json::to_string(&Reason::Dependency).unwrap(),
+        "\"dependency\""
+    );
+}
+
+#[test]
+fn parse_packager() {
+    let desc = parse_with_sections("%PACKAGER%\nJane Doe <jane@archlinux.org>\n\n");
+    assert_eq!(
+        desc.packager.as_deref(),
+        Some("Jane Doe <jane@archlinux.org>")
+    );
+    assert_eq!(parse_with_sections("").packager, None);
+}
+
+#[test]
+fn parse_arch() {
+    let desc = parse_with_sections("%ARCH%\nsome-new-arch\n\n");
+    assert_eq!(desc.arch.as_deref(), Some("some-new-arch"));
+    assert_eq!(parse_with_sections("").arch, None);
+}
+
+#[test]
+fn parse_install_date() {
+    let desc = parse_with_sections("%INSTALLDATE%\n1700000000\n\n");
+    assert_eq!(desc.install_date, Some(1700000000));
+    assert_eq!(parse_with_sections("").install_date, None);
+}
+
+#[test]
+fn parse_build_date() {
+    let desc = parse_with_sections("%BUILDDATE%\n1600000000\n\n");
+    assert_eq!(desc.build_date, Some(1600000000));
+    assert_eq!(parse_with_sections("").build_date, None);
+}
+
+#[test]
+fn parse_provides() {
+    let desc = parse_with_sections("%PROVIDES%\nmime-types\nfoo=1.2\n\n");
+    assert_eq!(desc.provides, ["mime-types", "foo=1.2"]);
+}
+
+#[test]
+fn parse_conflicts() {
+    let desc = parse_with_sections("%CONFLICTS%\nfoo<2.0\nbar\n\n");
+    assert_eq!(desc.conflicts, ["foo<2.0", "bar"]);
+    assert!(parse_with_sections("").conflicts.is_empty());
+}
+
+#[test]
+fn parse_replaces() {
+    let desc = parse_with_sections("%REPLACES%\nold-name\n\n");
+    assert_eq!(desc.replaces, ["old-name"]);
+    assert!(parse_with_sections("").replaces.is_empty());
+}
+
+#[test]
+fn parse_groups() {
+    let desc = parse_with_sections("%GROUPS%\nbase\ngnome\n\n");
+    assert_eq!(desc.groups, ["base", "gnome"]);
+}
+
+#[test]
+fn parse_license() {
+    let desc = parse_with_sections("%LICENSE%\nGPL3\ncustom\n\n");
+    assert_eq!(desc.license, ["GPL3", "custom"]);
+    assert!(parse_with_sections("").license.is_empty());
+}
+
+// The values of a generated desc file, `None` for sections that are left out.
+#[cfg(test)]
+#[derive(Clone, Debug)]
+struct Sections {
+    name: String,
+    version: String,
+    description: Option<String>,
+    url: Option<String>,
+    arch: Option<String>,
+    dependency: Option<bool>,
+    size: Option<u64>,
+    install_date: Option<i64>,
+    dependencies: Option<Vec<String>>,
+    provides: Option<Vec<String>>,
+    groups: Option<Vec<String>>,
+    // unknown to the parser
+    validation: Option<Vec<String>>,
+}
+
+#[cfg(test)]
+impl Sections {
+    fn strategy() -> impl proptest::strategy::Strategy<Value = Self> {
+        use proptest::prelude::*;
+        // a value like the ones in desc files, never empty and without newlines, starting with a
+        // letter like names do
+        let word = || "[a-z][a-z0-9._+:<>= -]{0,11}";
+        let words = move || proptest::collection::vec(word(), 1..=5);
+        (
+            (word(), word()),
+            (
+                proptest::option::of(word()),
+                proptest::option::of(word()),
+                proptest::option::of(word()),
+            ),
+            (
+                proptest::option::of(any::<bool>()),
+                proptest::option::of(0..=u64::MAX >> 1),
+                proptest::option::of(0..1i64 << 32),
+            ),
+            (
+                proptest::option::of(words()),
+                proptest::option::of(words()),
+                proptest::option::of(words()),
+                proptest::option::of(words()),
+            ),
+        )
+            .prop_map(
+                |(
+                    (name, version),
+                    (description, url, arch),
+                    (dependency, size, install_date),
+                    (dependencies, provides, groups, validation),
+                )| Self {
+                    name,
+                    version,
+                    description,
+                    url,
+                    arch,
+                    dependency,
+                    size,
+                    install_date,
+                    dependencies,
+                    provides,
+                    groups,
+                    validation,
+                },
+            )
+    }
+
+    // in the order of the file, before shuffling
+    fn texts(&self) -> Vec<String> {
+        let mut texts = vec![
+            format!("%NAME%\n{}", self.name),
+            format!("%VERSION%\n{}", self.version),
+        ];
+        let lines = |name: &str, value: &Option<Vec<String>>| {
+            value
+                .as_ref()
+                .map(|value| format!("%{name}%\n{}", value.join("\n")))
+        };
+        texts.extend(
+            [
+                self.description.as_ref().map(|d| format!("%DESC%\n{d}")),
+                self.url.as_ref().map(|url| format!("%URL%\n{url}")),
+                self.arch.as_ref().map(|arch| format!("%ARCH%\n{arch}")),
+                self.dependency.map(|d| format!("%REASON%\n{}", d as u8)),
+                self.size.map(|size| format!("%SIZE%\n{size}")),
+                self.install_date
+                    .map(|date| format!("%INSTALLDATE%\n{date}")),
+                lines("DEPENDS", &self.dependencies),
+                lines("PROVIDES", &self.provides),
+                lines("GROUPS", &self.groups),
+                lines("VALIDATION", &self.validation),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+        texts
+    }
+}
+
+// A well-formed desc file with random optional sections in random order, unknown sections
+// included, and what it contains.
+#[cfg(test)]
+fn desc_strategy() -> impl proptest::strategy::Strategy<Value = (String, Sections)> {
+    use proptest::prelude::*;
+    Sections::strategy()
+        .prop_flat_map(|sections| (Just(sections.texts()).prop_shuffle(), Just(sections)))
+        .prop_map(|(texts, sections)| {
+            let mut desc = texts.join("\n\n");
+            desc.push_str("\n\n");
+            (desc, sections)
+        })
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn parse_random_desc((desc, expected) in desc_strategy()) {
+        let parsed = PackageDesc::parse(&desc).unwrap_or_else(|err| panic!("{err:?}\n{desc}"));
+        assert_eq!(parsed.name, expected.name);
+        assert_eq!(parsed.version, expected.version);
+        assert_eq!(parsed.description, expected.description);
+        assert_eq!(parsed.url, expected.url);
+        assert_eq!(parsed.arch, expected.arch);
+        assert_eq!(
+            matches!(parsed.reason, Reason::Dependency),
+            expected.dependency == Some(true)
+        );
+        assert_eq!(parsed.size, expected.size);
+        assert_eq!(parsed.install_date, expected.install_date);
+        let dependencies: Vec<Dependency> = expected
+            .dependencies
+            .iter()
+            .flatten()
+            .map(|d| Dependency::parse(d))
+            .collect();
+        assert_eq!(parsed.dependencies, dependencies);
+        assert_eq!(parsed.provides, expected.provides.unwrap_or_default());
+        assert_eq!(parsed.groups, expected.groups.unwrap_or_default());
+        assert!(parsed.warnings.is_empty());
+    }
+
+    // Damaged files have to be reported as errors instead of crashing. The required sections can be
+    // damaged too, so only some of them still parse, but those have the required fields.
+    #[test]
+    fn parse_damaged_desc(
+        (desc, _) in desc_strategy(),
+        edits in proptest::collection::vec(
+            (
+                0..3,
+                proptest::prelude::any::<proptest::sample::Index>(),
+                proptest::sample::select(vec!['\n', '%', 'x', '9', '-']),
+            ),
+            1..=4,
+        ),
+    ) {
+        let mut chars: Vec<char> = desc.chars().collect();
+        for (edit, index, char) in edits {
+            if chars.is_empty() {
+                break;
+            }
+            let i = index.index(chars.len());
+            match edit {
+                0 => {
+                    chars.remove(i);
+                }
+                1 => chars.insert(i, char),
+                _ => chars.truncate(i),
+            }
+        }
+        let damaged: String = chars.into_iter().collect();
+        if let Ok(parsed) = PackageDesc::parse(&damaged) {
+            assert!(!parsed.name.is_empty(), "{damaged}");
+            assert!(!parsed.version.is_empty(), "{damaged}");
+        }
+    }
+}
+
+#[test]
+fn parse_invalid_desc() {
+    assert!(PackageDesc::parse("").is_err());
+    assert!(PackageDesc::parse("%NAME%\n\n%VERSION%\n1\n\n").is_err());
+    assert!(PackageDesc::parse("%NAME%\na\n\n%SIZE%\nbig\n\n%VERSION%\n1\n\n").is_err());
+}
+
 // Code to load a pacman database in memory. Unused but wanted to remember it.
 /*
 fn parse_pacman_db() {
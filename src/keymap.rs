@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use crossterm::event::KeyCode;
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    NextColumn,
+    PreviousColumn,
+    MoveUp,
+    MoveDown,
+    PageUp,
+    PageDown,
+    Start,
+    End,
+    Focus,
+    Peek,
+    ReturnFromPeek,
+    CycleSort,
+    ToggleExplicit,
+    Search,
+    Filter,
+    Group,
+    MinSize,
+    Export,
+    ExportCsv,
+    NextMatch,
+    PreviousMatch,
+    ToggleCase,
+    CycleSearchMode,
+    CycleSearchScope,
+    ToggleOrphans,
+    ToggleLeaves,
+    ToggleRemoval,
+    ToggleOptional,
+    ToggleUnits,
+    Back,
+    Forward,
+    Reload,
+    ToggleFiles,
+    CycleDetails,
+    OpenUrl,
+    ToggleTree,
+    ToggleExpand,
+    ToggleSubtree,
+    ToggleHelp,
+    Quit,
+}
+
+// every action with its name in the config file and its default keys
+const ACTIONS: &[(Action, &str, &[KeyCode])] = &[
+    (
+        Action::MoveLeft,
+        "move-left",
+        &[KeyCode::Left, KeyCode::Char('h')],
+    ),
+    (
+        Action::MoveRight,
+        "move-right",
+        &[KeyCode::Right, KeyCode::Char('l')],
+    ),
+    (Action::NextColumn, "next-column", &[KeyCode::Tab]),
+    (
+        Action::PreviousColumn,
+        "previous-column",
+        &[KeyCode::BackTab],
+    ),
+    (
+        Action::MoveUp,
+        "move-up",
+        &[KeyCode::Up, KeyCode::Char('k')],
+    ),
+    (
+        Action::MoveDown,
+        "move-down",
+        &[KeyCode::Down, KeyCode::Char('j')],
+    ),
+    (Action::PageUp, "page-up", &[KeyCode::PageUp]),
+    (Action::PageDown, "page-down", &[KeyCode::PageDown]),
+    (Action::Start, "start", &[KeyCode::Char('1'), KeyCode::Home]),
+    (Action::End, "end", &[KeyCode::Char('0'), KeyCode::End]),
+    (Action::Focus, "focus", &[KeyCode::Enter]),
+    (Action::Peek, "peek", &[KeyCode::Char('p')]),
+    (
+        Action::ReturnFromPeek,
+        "return-from-peek",
+        &[KeyCode::Char('P')],
+    ),
+    (Action::CycleSort, "cycle-sort", &[KeyCode::Char('s')]),
+    (
+        Action::ToggleExplicit,
+        "toggle-explicit",
+        &[KeyCode::Char('e')],
+    ),
+    (Action::Search, "search", &[KeyCode::Char('/')]),
+    (Action::Filter, "filter", &[KeyCode::Char('|')]),
+    (Action::Group, "group", &[KeyCode::Char('g')]),
+    (Action::MinSize, "min-size", &[KeyCode::Char('>')]),
+    (Action::Export, "export", &[KeyCode::Char('x')]),
+    (Action::ExportCsv, "export-csv", &[KeyCode::Char('X')]),
+    (Action::NextMatch, "next-match", &[KeyCode::Char('n')]),
+    (
+        Action::PreviousMatch,
+        "previous-match",
+        &[KeyCode::Char('N')],
+    ),
+    (Action::ToggleCase, "toggle-case", &[KeyCode::Char('i')]),
+    (
+        Action::CycleSearchMode,
+        "cycle-search-mode",
+        &[KeyCode::Char('m')],
+    ),
+    (
+        Action::CycleSearchScope,
+        "cycle-search-scope",
+        &[KeyCode::Char('f')],
+    ),
+    (
+        Action::ToggleOrphans,
+        "toggle-orphans",
+        &[KeyCode::Char('o')],
+    ),
+    (Action::ToggleLeaves, "toggle-leaves", &[KeyCode::Char('L')]),
+    (
+        Action::ToggleRemoval,
+        "toggle-removal",
+        &[KeyCode::Char('R')],
+    ),
+    (
+        Action::ToggleOptional,
+        "toggle-optional",
+        &[KeyCode::Char('O')],
+    ),
+    (Action::ToggleUnits, "toggle-units", &[KeyCode::Char('u')]),
+    (
+        Action::Back,
+        "back",
+        &[KeyCode::Backspace, KeyCode::Char('[')],
+    ),
+    (Action::Forward, "forward", &[KeyCode::Char(']')]),
+    (
+        Action::Reload,
+        "reload",
+        &[KeyCode::Char('r'), KeyCode::F(5)],
+    ),
+    (Action::ToggleFiles, "toggle-files", &[KeyCode::Char('F')]),
+    (Action::CycleDetails, "cycle-details", &[KeyCode::Char('D')]),
+    (Action::OpenUrl, "open-url", &[KeyCode::Char('w')]),
+    (Action::ToggleTree, "toggle-tree", &[KeyCode::Char('t')]),
+    (Action::ToggleExpand, "toggle-expand", &[KeyCode::Char(' ')]),
+    (
+        Action::ToggleSubtree,
+        "toggle-subtree",
+        &[KeyCode::Char('Z')],
+    ),
+    (Action::ToggleHelp, "toggle-help", &[KeyCode::Char('?')]),
+    (Action::Quit, "quit", &[KeyCode::Char('q')]),
+];
+
+// a single key or a list of keys
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+// Which keys trigger which action. Indexed like `ACTIONS`. Deserialized from bindings like
+// `Keymap::new` takes.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "BTreeMap<String, Keys>")]
+pub struct Keymap {
+    keys: Vec<Vec<KeyCode>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            keys: ACTIONS.iter().map(|(_, _, keys)| keys.to_vec()).collect(),
+        }
+    }
+}
+
+impl TryFrom<BTreeMap<String, Keys>> for Keymap {
+    type Error = anyhow::Error;
+
+    fn try_from(bindings: BTreeMap<String, Keys>) -> Result<Self> {
+        Self::new(bindings)
+    }
+}
+
+impl Keymap {
+    // Actions named in `bindings` get the given keys instead of their defaults. Keys given to an
+    // action are taken away from the defaults of other actions.
+    pub fn new(bindings: BTreeMap<String, Keys>) -> Result<Self> {
+        let mut configured = BTreeMap::new();
+        for (name, keys) in bindings {
+            let index = ACTIONS
+                .iter()
+                .position(|(_, name_, _)| *name_ == name)
+                .with_context(|| format!("unknown action {name:?}"))?;
+            let keys = match keys {
+                Keys::One(key) => vec![key],
+                Keys::Many(keys) => keys,
+            };
+            let keys = keys
+                .iter()
+                .map(|key| parse_key(key))
+                .collect::<Result<Vec<_>>>()?;
+            configured.insert(index, keys);
+        }
+        let mut keymap = Self::default();
+        for (index, keys) in keymap.keys.iter_mut().enumerate() {
+            match configured.get(&index) {
+                Some(configured_keys) => keys.clone_from(configured_keys),
+                None => keys.retain(|key| !configured.values().any(|keys| keys.contains(key))),
+            }
+        }
+        Ok(keymap)
+    }
+
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        let index = self.keys.iter().position(|keys| keys.contains(&key))?;
+        Some(ACTIONS[index].0)
+    }
+
+    // The keys of the actions for the help, like `r, F5`.
+    pub fn describe(&self, actions: &[Action]) -> String {
+        let names: Vec<String> = actions
+            .iter()
+            .flat_map(|action| {
+                // Unwrap because every action is in `ACTIONS`.
+                let index = ACTIONS.iter().position(|(a, _, _)| a == action).unwrap();
+                self.keys[index].iter().map(|key| key_name(*key))
+            })
+            .collect();
+        match names.is_empty() {
+            true => "unbound".to_string(),
+            false => names.join(", "),
+        }
+    }
+}
+
+// A single character or the name of a special key like `Enter` or `F5`, ignoring case.
+fn parse_key(key: &str) -> Result<KeyCode> {
+    let mut chars = key.chars();
+    if let (Some(char), None) = (chars.next(), chars.next()) {
+        return Ok(KeyCode::Char(char));
+    }
+    let lowercase = key.to_lowercase();
+    Ok(match lowercase.as_str() {
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "pgup" | "pageup" => KeyCode::PageUp,
+        "pgdown" | "pagedown" => KeyCode::PageDown,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "enter" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "tab" => KeyCode::Tab,
+        "backtab" | "shift-tab" => KeyCode::BackTab,
+        "space" => KeyCode::Char(' '),
+        function => match function.strip_prefix('f').and_then(|n| n.parse().ok()) {
+            Some(n @ 1..=12) => KeyCode::F(n),
+            _ => bail!("unknown key {key:?}"),
+        },
+    })
+}
+
+fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(char) => char.to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::PageUp => "PgUp".to_string(),
+        KeyCode::PageDown => "PgDown".to_string(),
+        KeyCode::BackTab => "Shift-Tab".to_string(),
+        KeyCode::F(n) => format!("F{n}"),
+        key => format!("{key:?}"),
+    }
+}
+
+#[cfg(test)]
+fn parse(bindings: &str) -> Result<Keymap> {
+    Keymap::new(toml::from_str(bindings)?)
+}
+
+#[test]
+fn keymap_defaults() {
+    let keymap = Keymap::default();
+    assert_eq!(keymap.action(KeyCode::Char('j')), Some(Action::MoveDown));
+    assert_eq!(keymap.action(KeyCode::F(5)), Some(Action::Reload));
+    assert_eq!(
+        keymap.action(KeyCode::BackTab),
+        Some(Action::PreviousColumn)
+    );
+    assert_eq!(keymap.action(KeyCode::Char('z')), None);
+    assert_eq!(keymap.describe(&[Action::Reload]), "r, F5");
+    assert_eq!(
+        keymap.describe(&[Action::PageUp, Action::PageDown]),
+        "PgUp, PgDown"
+    );
+    assert_eq!(parse("").unwrap(), keymap);
+}
+
+#[test]
+fn keymap_overrides() {
+    let keymap = parse("quit = \"Esc\"\nmove-down = [\"d\", \"down\"]\nback = \"j\"").unwrap();
+    assert_eq!(keymap.action(KeyCode::Esc), Some(Action::Quit));
+    assert_eq!(keymap.action(KeyCode::Char('q')), None);
+    assert_eq!(keymap.action(KeyCode::Char('d')), Some(Action::MoveDown));
+    assert_eq!(keymap.action(KeyCode::Char('j')), Some(Action::Back));
+    assert_eq!(keymap.action(KeyCode::Backspace), None);
+    // unconfigured actions lose keys that were given to other actions
+    let keymap = parse("quit = \"s\"").unwrap();
+    assert_eq!(keymap.action(KeyCode::Char('s')), Some(Action::Quit));
+    assert_eq!(keymap.describe(&[Action::CycleSort]), "unbound");
+    // unconfigured actions keep their defaults
+    assert_eq!(
+        keymap.action(KeyCode::Char('o')),
+        Some(Action::ToggleOrphans)
+    );
+    assert!(parse("fly = \"f\"").is_err());
+    assert!(parse("quit = \"hyper\"").is_err());
+}
@@ -0,0 +1,13 @@
+//! Parsing of the pacman local database, for example `/var/lib/pacman/local`, and the dpkg database
+//! without the terminal interface.
+
+pub mod backend;
+pub mod dependencies;
+pub mod dpkg;
+pub mod installed_packages;
+
+pub use backend::{PackageBackend, Pacman};
+pub use dpkg::Dpkg;
+pub use installed_packages::{
+    dependency_name, files, from_directory, Dependency, OptionalDependency, PackageDesc, Reason,
+};
@@ -1,101 +1,535 @@
 // todos:
-// - handle %PROVIDES%, for example mailcap provides mime-types
 // - better error handling
-// - figure out reasonable way to do logging, maybe print after main ends or detect whether stderr is tty
 
-// ideas:
-// - mode where left side shows only explicit installed and is recursive so you get a list of all packages
-//   you'd have to remove
+mod args;
+mod cache;
+mod config;
+mod keymap;
+mod preferences;
+mod search;
+mod xdg;
 
-mod installed_packages;
-
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+};
 
 use anyhow::{Context, Result};
+use cache::Cache;
+use config::Config;
 use crossterm::{
-    event::{Event, KeyCode},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
     terminal::{EnterAlternateScreen, LeaveAlternateScreen},
 };
-use installed_packages::{PackageDesc, Reason};
+use keymap::{Action, Keymap};
+use leptohadron::{
+    dependencies::resolve_dependency,
+    installed_packages::{PackageDesc, Reason},
+    Dpkg, PackageBackend, Pacman,
+};
+use preferences::Preferences;
+use search::{Matcher, SearchMode, SearchScope};
+use serde::{Deserialize, Serialize};
 use tui::{
     backend::Backend,
+    buffer::Buffer,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Modifier, Style},
-    text::{Span, Spans, Text},
-    widgets::{Block, BorderType, Borders, List, ListItem, ListState, Paragraph, Row, Table, Wrap},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{
+        Block, BorderType, Borders, List, ListItem, ListState, Paragraph, Row, Table, Widget, Wrap,
+    },
     Frame, Terminal,
 };
 
-const HELP: &[(&str, &str)] = &[
-    ("left, right", "move between lists"),
-    ("up, down, PgUp, PgDown", "move in list"),
-    ("1, 0", "move to start/end of list"),
-    ("Enter", "focus center list on selected entry"),
+// The keys of the actions are looked up in the keymap, rows without actions are for keys that no
+// action is bound to.
+const HELP: &[(&[Action], &str)] = &[
+    (&[Action::MoveLeft, Action::MoveRight], "move between lists"),
+    (
+        &[Action::NextColumn, Action::PreviousColumn],
+        "cycle through lists, wrapping around at the edges",
+    ),
+    (&[Action::MoveUp, Action::MoveDown, Action::PageUp, Action::PageDown], "move in list"),
+    (&[Action::Start, Action::End], "move to start/end of list"),
+    (&[Action::Focus], "focus center list on selected entry"),
+    (
+        &[Action::Peek],
+        "show selected entry of a side list in center list while staying in the side list",
+    ),
+    (&[Action::ReturnFromPeek], "return to the main package and list from before peeking"),
+    (
+        &[Action::CycleSort],
+        "cycle sorting between alphabetical-asc, size-desc, install-date-desc, dependants-desc and dependencies-desc in all lists",
+    ),
+    (&[Action::ToggleExplicit], "toggle showing only explicitly installed packages in main view"),
+    (&[Action::Search], "start entering search term, enter to search, esc to cancel"),
+    (
+        &[Action::Filter],
+        "start entering search term to narrow main view to matches while typing, enter to keep, esc to show all again",
+    ),
+    (
+        &[Action::Group],
+        "start entering group to show in main view, enter to filter, empty to remove group filter",
+    ),
+    (
+        &[Action::MinSize],
+        "start entering minimum size like 100M to show in main view, empty to remove size filter",
+    ),
+    (&[Action::Export], "start entering path to export main view to as JSON, enter to write"),
+    (&[Action::ExportCsv], "start entering path to export main view to as CSV, enter to write"),
+    (&[Action::NextMatch], "go to next search match downwards in active view"),
+    (&[Action::PreviousMatch], "go to next search match upwards in active view"),
+    (&[Action::ToggleCase], "toggle case sensitive search"),
+    (&[Action::CycleSearchMode], "cycle search mode between substring, fuzzy and regex"),
+    (
+        &[Action::CycleSearchScope],
+        "cycle searched fields between name, name and description, and all",
+    ),
+    (&[Action::ToggleOrphans], "toggle showing only orphans in main view"),
+    (
+        &[Action::ToggleLeaves],
+        "toggle showing only packages no installed package requires, also explicitly installed ones",
+    ),
+    (
+        &[Action::ToggleRemoval],
+        "toggle right list between dependencies and what removing the main package would also remove",
+    ),
+    (
+        &[Action::ToggleOptional],
+        "toggle listing optional dependencies separately after required ones",
+    ),
+    (&[Action::ToggleUnits], "toggle sizes between binary units like pacman and decimal units"),
     (
-        "s",
-        "toggle sorting between alphabetical-asc and size-desc in active view",
+        &[],
+        "type start of a name with keys without an action to jump to the next package with it in active view",
     ),
+    (&[Action::Back], "go back to the previous main package"),
+    (&[Action::Forward], "go forward to the main package that was left by going back"),
+    (&[Action::Reload], "reload the database, for example after installing packages"),
+    (&[Action::ToggleFiles], "toggle details between information and installed files"),
+    (&[Action::CycleDetails], "cycle details between below every list, below all lists and hidden"),
+    (&[Action::OpenUrl], "open url of selected package in browser"),
+    (&[Action::ToggleTree], "toggle dependency tree of the selected package"),
     (
-        "e",
-        "toggle showing only explicitly installed packages in main view",
+        &[Action::ToggleSubtree],
+        "toggle main view between everything the selected package depends on and the previous view, also Esc",
     ),
     (
-        "/",
-        "start entering search term, enter to search, esc to cancel",
+        &[Action::ToggleExpand],
+        "expand or collapse the selected package in the dependency tree, also Enter",
     ),
-    ("n", "go to next search match downwards"),
-    ("N", "go to next search match upwards"),
-    ("?", "toggle help"),
-    ("q", "quit"),
+    (&[Action::ToggleHelp], "toggle help"),
+    (&[Action::Quit], "quit, also Ctrl-C"),
 ];
 
-#[derive(Clone, Copy, Default)]
+// Where the details of the selected packages are shown.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+enum DetailsLayout {
+    // below the list in every column
+    #[default]
+    Columns,
+    // across the bottom for the active column, so the lists are taller
+    Bottom,
+    // lists take all the space
+    Hidden,
+}
+
+impl DetailsLayout {
+    fn next(&self) -> Self {
+        match self {
+            DetailsLayout::Columns => DetailsLayout::Bottom,
+            DetailsLayout::Bottom => DetailsLayout::Hidden,
+            DetailsLayout::Hidden => DetailsLayout::Columns,
+        }
+    }
+}
+
+// Below this width and height nothing but a message is drawn because the lists wouldn't be usable.
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 8;
+
+// The help is hidden when it would leave the lists fewer lines than this, borders included.
+const MIN_LIST_HEIGHT: u16 = 10;
+
+// how long typing pauses before jumping starts over with a new prefix
+const SEEK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
 enum SortCritera {
     #[default]
     NameAsc,
     SizeDesc,
+    InstallDateDesc,
+    // number of installed packages depending on the package
+    DependantsDesc,
+    // number of dependencies and optional dependencies
+    DependenciesDesc,
 }
 
 impl SortCritera {
-    fn sort(&self, packages: &mut [&PackageDesc]) {
+    // Ties are ordered by name so that the order doesn't depend on the previous one.
+    fn sort(&self, packages: &mut [&PackageDesc], dependants: &BTreeMap<&str, BTreeSet<&str>>) {
+        let dependants_count = |package: &PackageDesc| {
+            dependants
+                .get(package.name.as_str())
+                .map(BTreeSet::len)
+                .unwrap_or(0)
+        };
+        let dependencies_count = |package: &PackageDesc| {
+            package.dependencies.len() + package.optional_dependencies.len()
+        };
+        packages.sort_by(|a, b| {
+            let primary = match self {
+                SortCritera::NameAsc => std::cmp::Ordering::Equal,
+                SortCritera::SizeDesc => b.size.unwrap_or(0).cmp(&a.size.unwrap_or(0)),
+                // Reversing puts `None` last because it compares less than `Some`.
+                SortCritera::InstallDateDesc => b.install_date.cmp(&a.install_date),
+                SortCritera::DependantsDesc => dependants_count(b).cmp(&dependants_count(a)),
+                SortCritera::DependenciesDesc => dependencies_count(b).cmp(&dependencies_count(a)),
+            };
+            primary.then_with(|| natural_cmp(a.name.as_str(), b.name.as_str()))
+        });
+    }
+
+    fn next(&self) -> Self {
         match self {
-            SortCritera::NameAsc => packages.sort_by_key(|package| package.name.as_str()),
-            SortCritera::SizeDesc => {
-                packages.sort_by_key(|package| std::cmp::Reverse(package.size.unwrap_or(0)))
+            SortCritera::NameAsc => SortCritera::SizeDesc,
+            SortCritera::SizeDesc => SortCritera::InstallDateDesc,
+            SortCritera::InstallDateDesc => SortCritera::DependantsDesc,
+            SortCritera::DependantsDesc => SortCritera::DependenciesDesc,
+            SortCritera::DependenciesDesc => SortCritera::NameAsc,
+        }
+    }
+}
+
+// Compares strings like `Ord` but runs of digits by their numeric value, so that `gtk2` comes before
+// `gtk10`.
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek(), b.peek()) {
+            (Some(a_), Some(b_)) if a_.is_ascii_digit() && b_.is_ascii_digit() => {
+                let digits = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(char) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(char);
+                    }
+                    digits
+                };
+                let (a_digits, b_digits) = (digits(&mut a), digits(&mut b));
+                let (a_number, b_number) = (
+                    a_digits.trim_start_matches('0'),
+                    b_digits.trim_start_matches('0'),
+                );
+                // Without leading zeros the longer number is larger. Fall back to the digits so
+                // that `01` and `1` don't compare equal.
+                let ordering = a_number
+                    .len()
+                    .cmp(&b_number.len())
+                    .then_with(|| a_number.cmp(b_number))
+                    .then_with(|| a_digits.cmp(&b_digits));
+                if ordering.is_ne() {
+                    return ordering;
+                }
             }
-        };
+            (Some(_), Some(_)) => {
+                // Unwrap because both were peeked.
+                let ordering = a.next().unwrap().cmp(&b.next().unwrap());
+                if ordering.is_ne() {
+                    return ordering;
+                }
+            }
+            (a_, b_) => return a_.is_some().cmp(&b_.is_some()),
+        }
+    }
+}
+
+#[test]
+fn natural_cmp_numbers() {
+    let mut names = [
+        "gtk10",
+        "gtk3",
+        "gtk",
+        "gtk2",
+        "gtk2-extra",
+        "python3.10",
+        "python3.9",
+    ];
+    names.sort_by(|a, b| natural_cmp(a, b));
+    assert_eq!(
+        names,
+        [
+            "gtk",
+            "gtk2",
+            "gtk2-extra",
+            "gtk3",
+            "gtk10",
+            "python3.9",
+            "python3.10"
+        ]
+    );
+    assert!(natural_cmp("a01", "a1").is_lt());
+    assert!(natural_cmp("a1", "a1").is_eq());
+}
+
+#[test]
+fn sort_install_date_unknown_last() {
+    let packages: Vec<PackageDesc> = [Some(1), None, Some(2)]
+        .into_iter()
+        .map(|install_date| PackageDesc {
+            install_date,
+            ..Default::default()
+        })
+        .collect();
+    let mut packages: Vec<&PackageDesc> = packages.iter().collect();
+    SortCritera::InstallDateDesc.sort(packages.as_mut_slice(), &BTreeMap::new());
+    let dates: Vec<_> = packages
+        .iter()
+        .map(|package| package.install_date)
+        .collect();
+    assert_eq!(dates, [Some(2), Some(1), None]);
+}
+
+#[test]
+fn sort_ties_by_name() {
+    let mut packages = [
+        test_package("d", Reason::Explicit, &[], &[]),
+        test_package("c", Reason::Explicit, &[], &[]),
+        test_package("b", Reason::Explicit, &[], &[]),
+        test_package("a", Reason::Explicit, &[], &[]),
+        test_package("lib10", Reason::Explicit, &[], &[]),
+        test_package("lib2", Reason::Explicit, &[], &[]),
+    ];
+    packages[1].size = Some(5);
+    packages[3].size = Some(5);
+    packages[0].size = Some(0);
+    let mut sorted: Vec<&PackageDesc> = packages.iter().collect();
+    SortCritera::SizeDesc.sort(sorted.as_mut_slice(), &BTreeMap::new());
+    let names: Vec<_> = sorted.iter().map(|package| package.name.as_str()).collect();
+    // no size counts as 0
+    assert_eq!(names, ["a", "c", "b", "d", "lib2", "lib10"]);
+    // the same whatever the order before
+    sorted.reverse();
+    SortCritera::SizeDesc.sort(sorted.as_mut_slice(), &BTreeMap::new());
+    let again: Vec<_> = sorted.iter().map(|package| package.name.as_str()).collect();
+    assert_eq!(again, names);
+}
+
+#[test]
+fn sort_dependants_desc() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["c"], &[]),
+        test_package("b", Reason::Explicit, &["c", "d"], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+        test_package("d", Reason::Dependency, &[], &[]),
+    ]);
+    let app = App::new(&packages);
+    let mut sorted: Vec<&PackageDesc> = packages.values().collect();
+    SortCritera::DependantsDesc.sort(sorted.as_mut_slice(), &app.dependants);
+    let names: Vec<_> = sorted.iter().map(|package| package.name.as_str()).collect();
+    assert_eq!(names, ["c", "d", "a", "b"]);
+}
+
+// Formats a unix timestamp as a date in the local time zone.
+fn format_date(timestamp: i64) -> String {
+    use chrono::TimeZone;
+    match chrono::Local.timestamp_opt(timestamp, 0).single() {
+        Some(date) => date.format("%Y-%m-%d").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+// Describes how long before `now` the timestamp was, like `3 days ago`.
+fn format_age(timestamp: i64, now: i64) -> String {
+    let seconds = now - timestamp;
+    let (count, unit) = match seconds {
+        ..0 => return "in the future".to_string(),
+        0..60 => return "just now".to_string(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        _ => match seconds / 86400 {
+            days @ 0..30 => (days, "day"),
+            days @ 30..365 => (days / 30, "month"),
+            days => (days / 365, "year"),
+        },
+    };
+    match count {
+        1 => format!("1 {unit} ago"),
+        _ => format!("{count} {unit}s ago"),
+    }
+}
+
+#[test]
+fn format_ages() {
+    let now = 1_700_000_000;
+    assert_eq!(format_age(now - 10, now), "just now");
+    assert_eq!(format_age(now - 60, now), "1 minute ago");
+    assert_eq!(format_age(now - 3 * 3600, now), "3 hours ago");
+    assert_eq!(format_age(now - 3 * 86400, now), "3 days ago");
+    assert_eq!(format_age(now - 65 * 86400, now), "2 months ago");
+    assert_eq!(format_age(now - 800 * 86400, now), "2 years ago");
+    assert_eq!(format_age(now + 10, now), "in the future");
+}
+
+// Like ` (4.1% of total)`, empty when nothing has a size.
+fn share_of_total(size: u64, total: u64) -> String {
+    match total {
+        0 => String::new(),
+        total => format!(" ({:.1}% of total)", size as f64 * 100.0 / total as f64),
     }
 }
 
-#[derive(Clone, Copy, Default)]
+#[test]
+fn share_of_total_guards_zero() {
+    assert_eq!(share_of_total(41, 1000), " (4.1% of total)");
+    assert_eq!(share_of_total(0, 0), "");
+}
+
+// Restricts the main view. Active filters are combined so that packages have to pass all of them.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 enum Filter {
-    #[default]
-    All,
     Explicit,
+    Group(String),
+    Orphans,
+    // not required by any installed package, whatever the reason
+    Leaves,
+    // bytes
+    MinSize(u64),
+    // packages matching the search term with the search settings
+    Search(String),
 }
 
 impl Filter {
-    fn filter(&self, package: &PackageDesc) -> bool {
+    fn filter(&self, package: &PackageDesc, dependants: &BTreeMap<&str, BTreeSet<&str>>) -> bool {
         match self {
-            Self::All => true,
             Self::Explicit => matches!(package.reason, Reason::Explicit),
+            Self::Group(group) => package.groups.contains(group),
+            Self::Orphans => is_orphan(package, dependants),
+            Self::Leaves => dependants
+                .get(package.name.as_str())
+                .is_none_or(BTreeSet::is_empty),
+            Self::MinSize(min) => package.size.is_some_and(|size| size >= *min),
+            // matched in `App::passes_filters` which has the search settings
+            Self::Search(_) => true,
+        }
+    }
+
+    fn name(&self, units: humansize::FormatSizeOptions) -> String {
+        match self {
+            Self::Explicit => "explicit".to_string(),
+            Self::Group(group) => format!("group {group}"),
+            Self::Orphans => "orphans".to_string(),
+            Self::Leaves => "leaves".to_string(),
+            Self::MinSize(min) => format!(">= {}", humansize::SizeFormatter::new(*min, units)),
+            Self::Search(term) => format!("matching {term:?}"),
         }
     }
+
+    // whether no package can pass both filters
+    fn excludes(&self, other: &Filter) -> bool {
+        // orphans are installed as dependencies
+        matches!(
+            (self, other),
+            (Self::Explicit, Self::Orphans) | (Self::Orphans, Self::Explicit)
+        )
+    }
+}
+
+// Parses sizes like `100M` or `2GiB` into bytes. Suffixes without `i` are decimal.
+fn parse_size(size: &str) -> Option<u64> {
+    let split = size
+        .find(|char: char| !char.is_ascii_digit() && char != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let unit = unit.trim();
+    let unit = unit.strip_suffix(['B', 'b']).unwrap_or(unit);
+    let (unit, base) = match unit.strip_suffix('i') {
+        Some(unit) => (unit, 1024f64),
+        None => (unit, 1000f64),
+    };
+    let exponent = match unit.to_ascii_uppercase().as_str() {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return None,
+    };
+    Some((number * base.powi(exponent)) as u64)
+}
+
+#[test]
+fn parse_sizes() {
+    assert_eq!(parse_size("100"), Some(100));
+    assert_eq!(parse_size("100B"), Some(100));
+    assert_eq!(parse_size("100M"), Some(100_000_000));
+    assert_eq!(parse_size("1.5 GB"), Some(1_500_000_000));
+    assert_eq!(parse_size("2Gi"), Some(2 << 30));
+    assert_eq!(parse_size("2k"), Some(2000));
+    assert_eq!(parse_size("1KiB"), Some(1024));
+    assert_eq!(parse_size("M"), None);
+    assert_eq!(parse_size("1X"), None);
+}
+
+// An orphan is a package that was installed as a dependency but is no longer required by any installed
+// package, like `pacman -Qdt`.
+fn is_orphan(package: &PackageDesc, dependants: &BTreeMap<&str, BTreeSet<&str>>) -> bool {
+    matches!(package.reason, Reason::Dependency)
+        && dependants
+            .get(package.name.as_str())
+            .is_none_or(BTreeSet::is_empty)
 }
 
 #[derive(Default)]
 struct Column<'a> {
-    title: &'static str,
+    title: String,
     is_active: bool,
     sort_criteria: SortCritera,
     packages: Vec<&'a PackageDesc>,
     // invariant: never has element selected that is out of range of `packages`
     // invariant: has no selection IFF packages is empty
     list_state: ListState,
+    // where the column and its list were last rendered, used to map mouse clicks to entries
+    area: Rect,
+    list_area: Rect,
+    // index of the first visible entry, follows the selection like `List` scrolling does
+    offset: usize,
+    // names of entries that are drawn dimmed
+    dimmed: BTreeSet<&'a str>,
+    // (index, score) of the entries matching the current search, best first, computed by
+    // `App::search` when the term or the entries change
+    search_matches: Option<Vec<(usize, i64)>>,
 }
 
 impl<'a> Column<'a> {
-    fn render(&mut self, frame: &mut Frame<impl Backend>, area: Rect) {
+    // Sorts by `sort_criteria` with the dimmed entries after the others.
+    fn sort(&mut self, dependants: &BTreeMap<&str, BTreeSet<&str>>) {
+        self.sort_criteria
+            .sort(self.packages.as_mut_slice(), dependants);
+        // stable so the sort order is kept within both parts
+        let dimmed = &self.dimmed;
+        self.packages
+            .sort_by_key(|package| dimmed.contains(package.name.as_str()));
+    }
+
+    // Returns the area below the list for the details of the selected package if `details`. Parts
+    // of names matching `search` are highlighted and packages installed as dependencies get
+    // `dependency_style`.
+    fn render(
+        &mut self,
+        frame: &mut Frame<impl Backend>,
+        area: Rect,
+        search: Option<&Matcher>,
+        match_style: Style,
+        dependency_style: Style,
+        details: bool,
+    ) -> Option<Rect> {
         let block = Block::default()
             .title(format!(
                 "{} {}/{}",
@@ -112,57 +546,107 @@ impl<'a> Column<'a> {
         let area_ = block.inner(area);
         frame.render_widget(block, area);
 
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Ratio(1, 2); 2])
-            .split(area_);
+        let chunks = match details {
+            true => Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Ratio(1, 2); 2])
+                .split(area_),
+            false => vec![area_],
+        };
 
-        let items: Vec<ListItem> = self
-            .packages
+        let list_area = if chunks[0].height > 0 && self.packages.len() > chunks[0].height as usize {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Length(1)])
+                .split(chunks[0]);
+            self.render_scrollbar(frame, chunks[1]);
+            chunks[0]
+        } else {
+            chunks[0]
+        };
+
+        // Only the visible entries are turned into items because lists can have many thousands.
+        self.update_offset(list_area.height as usize);
+        let end = (self.offset + list_area.height as usize).min(self.packages.len());
+        let items: Vec<ListItem> = self.packages[self.offset.min(end)..end]
             .iter()
-            .map(|desc| ListItem::new(Text::raw(desc.name.as_str())))
+            .map(|desc| {
+                let ranges = search
+                    .map(|matcher| matcher.match_ranges(desc.name.as_str()))
+                    .unwrap_or_default();
+                let style = match desc.reason {
+                    Reason::Explicit => Style::default(),
+                    Reason::Dependency => dependency_style,
+                };
+                let style = match self.dimmed.contains(desc.name.as_str()) {
+                    true => style.add_modifier(Modifier::DIM),
+                    false => style,
+                };
+                ListItem::new(highlight(desc.name.as_str(), &ranges, match_style)).style(style)
+            })
             .collect();
         let list = List::new(items).highlight_style(Style::default().add_modifier(Modifier::BOLD));
-        frame.render_stateful_widget(list, chunks[0], &mut self.list_state);
+        let mut state = ListState::default();
+        state.select(
+            self.list_state
+                .selected()
+                .map(|selected| selected - self.offset),
+        );
+        frame.render_stateful_widget(list, list_area, &mut state);
+        self.area = area;
+        self.list_area = list_area;
 
-        let selected = self.selected();
-        let mut text: Vec<Spans> = Default::default();
-        if let Some(selected) = selected {
-            let style = Style::default().add_modifier(Modifier::UNDERLINED);
-            text.push(Spans(vec![
-                Span::styled("name", style),
-                format!(":    {}", selected.name).into(),
-            ]));
-            text.push(Spans(vec![
-                Span::styled("version", style),
-                format!(": {}", selected.version).into(),
-            ]));
-            text.push(Spans(vec![
-                Span::styled("reason", style),
-                format!(":  {:?}", selected.reason).into(),
-            ]));
-            text.push(Spans(vec![
-                Span::styled("size", style),
-                format!(
-                    ":    {}",
-                    humansize::SizeFormatter::new(selected.size.unwrap_or(0), humansize::DECIMAL)
-                )
-                .into(),
-            ]));
-            text.push("".into());
-            text.push(Spans(vec![Span::styled("description", style), ":".into()]));
-            text.push(selected.description.as_str().into());
-            text.push("".into());
-            text.push(Spans(vec![Span::styled("url", style), ":".into()]));
-            text.push(selected.url.as_str().into());
+        chunks.get(1).copied()
+    }
+
+    // Draws a thumb whose position reflects the selection. Must only be called when the packages
+    // don't fit into the non empty area.
+    fn render_scrollbar(&self, frame: &mut Frame<impl Backend>, area: Rect) {
+        let height = area.height as usize;
+        let len = self.packages.len();
+        let thumb = (height * height / len).max(1);
+        let start = self.list_state.selected().unwrap_or(0) * (height - thumb) / (len - 1);
+        let lines: Vec<Spans> = (0..height)
+            .map(|i| match (start..start + thumb).contains(&i) {
+                true => "█".into(),
+                false => "│".into(),
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+
+    // Scrolls like `List` does when rendering: just enough to keep the selection visible.
+    fn update_offset(&mut self, height: usize) {
+        let selected = match self.list_state.selected() {
+            Some(selected) => selected,
+            None => {
+                self.offset = 0;
+                return;
+            }
+        };
+        let offset = self.offset.min(self.packages.len().saturating_sub(1));
+        self.offset = if selected >= offset + height {
+            selected + 1 - height
+        } else if selected < offset {
+            selected
+        } else {
+            offset
+        };
+    }
+
+    // returns the index of the entry rendered at the terminal position
+    fn entry_at(&self, column: u16, row: u16) -> Option<usize> {
+        if !contains(self.list_area, column, row) {
+            return None;
         }
-        let paragraph = Paragraph::new(text)
-            .wrap(Wrap { trim: false })
-            .block(Block::default().borders(Borders::TOP));
-        frame.render_widget(paragraph, chunks[1]);
+        let index = self.offset + (row - self.list_area.top()) as usize;
+        (index < self.packages.len()).then_some(index)
     }
 
     fn change(&mut self, distance: isize) -> bool {
+        if self.packages.is_empty() {
+            return false;
+        }
         let old = self.list_state.selected();
         let new = old
             .map(|i| (i as isize + distance).clamp(0, (self.packages.len() - 1) as isize) as usize);
@@ -170,11 +654,14 @@ impl<'a> Column<'a> {
         old != new
     }
 
-    fn after_packages_change(&mut self, previous_selection: Option<&'a PackageDesc>) {
+    // Selects the previously selected package again by name, which also works for a package from
+    // before reloading, or the first one if it is gone.
+    fn after_packages_change(&mut self, previous_selection: Option<&PackageDesc>) {
+        self.search_matches = None;
         let new_index = previous_selection.and_then(|package| {
             self.packages
                 .iter()
-                .position(|package_| std::ptr::eq(package, *package_))
+                .position(|package_| package_.name == package.name)
         });
         self.list_state
             .select(new_index.or_else(|| (!self.packages.is_empty()).then_some(0)));
@@ -187,6 +674,101 @@ impl<'a> Column<'a> {
     }
 }
 
+struct TreeRow<'a> {
+    package: &'a PackageDesc,
+    // names from the root to the package, identifies the row because packages can appear more than
+    // once
+    path: Vec<&'a str>,
+    // the package is its own ancestor so it can't be expanded
+    cycle: bool,
+    has_dependencies: bool,
+}
+
+// The dependencies of a package as an indented tree that is expanded one package at a time.
+struct Tree<'a> {
+    root: &'a PackageDesc,
+    // paths of the expanded rows
+    expanded: BTreeSet<Vec<&'a str>>,
+    // the visible rows from the top, built by `App::update_tree`
+    rows: Vec<TreeRow<'a>>,
+    // invariant: always has a row selected because the root is always visible
+    list_state: ListState,
+    // of the list when it was last drawn
+    height: u16,
+}
+
+// The main view limited to what a package pulls in, see `App::toggle_subtree`.
+#[derive(Clone)]
+struct Subtree<'a> {
+    root: &'a PackageDesc,
+    // the root and everything it transitively depends on
+    packages: BTreeSet<&'a str>,
+    // active before zooming in, restored when zooming out
+    filters: Vec<Filter>,
+}
+
+// A main package that was left by peeking at a package of a side list, see `App::peek`.
+struct Peek<'a> {
+    previous: &'a PackageDesc,
+    column: usize,
+    peeked: &'a PackageDesc,
+    // focusing the peeked package can remove filters and leave the subtree
+    filters: Vec<Filter>,
+    subtree: Option<Subtree<'a>>,
+}
+
+// What is kept when reloading the database.
+struct Settings {
+    main_package: Option<String>,
+    active_column: usize,
+    search: String,
+    preferences: Preferences,
+}
+
+#[test]
+fn cycle_columns() {
+    let packages = test_packages([test_package("a", Reason::Explicit, &[], &[])]);
+    let mut app = App::new(&packages);
+    let active = |app: &App| {
+        let active: Vec<bool> = app.columns.iter().map(|column| column.is_active).collect();
+        (app.active_column, active)
+    };
+    app.event(key(KeyCode::Tab));
+    assert_eq!(active(&app), (2, vec![false, false, true]));
+    app.event(key(KeyCode::Tab));
+    assert_eq!(active(&app), (0, vec![true, false, false]));
+    app.event(key(KeyCode::BackTab));
+    assert_eq!(active(&app), (2, vec![false, false, true]));
+    app.event(key(KeyCode::BackTab));
+    assert_eq!(active(&app), (1, vec![false, true, false]));
+}
+
+#[test]
+fn change_empty_column() {
+    let mut column = Column::default();
+    assert!(!column.change(1));
+    assert!(!column.change(-1));
+    assert_eq!(column.list_state.selected(), None);
+}
+
+#[derive(Clone, Copy)]
+enum Prompt {
+    Search,
+    // search term that narrows the main view while typing
+    Filter,
+    Group,
+    MinSize,
+    // path to write the main view to
+    Export(ExportFormat),
+}
+
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Json,
+    // name, version, size, reason and install date for spreadsheets
+    Csv,
+}
+
 enum SearchDirection {
     Up,
     Down,
@@ -194,72 +776,201 @@ enum SearchDirection {
 
 struct App<'a> {
     packages: &'a BTreeMap<String, PackageDesc>,
+    // virtual package name -> installed packages providing it
+    providers: BTreeMap<&'a str, BTreeSet<&'a str>>,
     dependants: BTreeMap<&'a str, BTreeSet<&'a str>>,
+    // without optional dependencies, for `removal_closure`
+    required_dependants: BTreeMap<&'a str, BTreeSet<&'a str>>,
     columns: [Column<'a>; 3],
     active_column: usize,
     show_help: bool,
-    filter: Filter,
-    // user is currently entering the search term
-    searching: bool,
-    // active search term
+    // whether the terminal was tall enough for the help when last drawn
+    help_fits: bool,
+    // all have to pass, at most one of each kind
+    filters: Vec<Filter>,
+    // all packages sorted for the main view, so that changing filters doesn't sort again
+    sorted_packages: Option<(SortCritera, Vec<&'a PackageDesc>)>,
+    // summed over all packages for the status bar
+    total_size: u64,
+    explicit_count: usize,
+    // how many entries a mouse wheel step moves
+    scroll_step: isize,
+    // how long reading the database took, for the status bar
+    load_time: std::time::Duration,
+    // packages that failed to load
+    skipped: usize,
+    // result of the last action, shown in the status bar until the next key press
+    message: Option<String>,
+    // problems that don't stop the session, printed after leaving the alternate screen
+    log: Vec<String>,
+    // sizes in KiB like pacman instead of kB
+    binary_units: bool,
+    // package name -> result of `total_size`
+    total_size_cache: RefCell<BTreeMap<String, u64>>,
+    // database directory, for reading file lists
+    backend: Box<dyn PackageBackend>,
+    // details show the installed files instead
+    show_files: bool,
+    details_layout: DetailsLayout,
+    // package name -> installed files or why they couldn't be read
+    files_cache: RefCell<BTreeMap<String, Result<Vec<String>, String>>>,
+    // right column lists optional dependencies dimmed after the required ones
+    separate_optional: bool,
+    // right column shows what would be removed along with the main package instead of dependencies
+    removal_mode: bool,
+    // what the user is currently entering text for
+    prompt: Option<Prompt>,
+    // active search term, edited in place by the search prompt
     search: String,
+    case_sensitive: bool,
+    search_mode: SearchMode,
+    search_scope: SearchScope,
+    // cached for `search`
+    matcher: Option<Matcher>,
+    // for `Filter::Search`, none if there is no such filter or its term is not a valid regex
+    filter_matcher: Option<Matcher>,
+    // why the entered text could not be used, shown in the prompt
+    prompt_error: Option<String>,
+    // text entered for the other prompts
+    input: String,
+    // the database should be read again after `event` returned
+    reload: bool,
+    // typed to jump to a package, see `seek`
+    seek_prefix: String,
+    // when the last character of the prefix was typed
+    seek_time: Option<std::time::Instant>,
+    // previous main packages, most recent last
+    back: Vec<&'a PackageDesc>,
+    // main packages that were left by going back, most recent last
+    forward: Vec<&'a PackageDesc>,
+    // left by peeking, most recent last
+    peeks: Vec<Peek<'a>>,
+    // the main packages reached by following dependants or dependencies, ending with the current
+    // one, shown as a breadcrumb
+    trail: Vec<&'a PackageDesc>,
+    keymap: Keymap,
+    // quitting asks for confirmation first
+    confirm_quit: bool,
+    // install date is shown relative to now
+    relative_dates: bool,
+    // sorting changes only the active column, otherwise all columns are sorted the same
+    per_column_sort: bool,
+    // what all columns are sorted by unless `per_column_sort`
+    sort_criteria: SortCritera,
+    // entries PgUp and PgDown move, see `page_size`
+    page_size: Option<usize>,
+    // asked whether to quit, the next key answers
+    confirming_quit: bool,
+    // quit with Ctrl-C, which picks nothing for `--print-selection`
+    interrupted: bool,
+    // shown instead of the lists when enabled
+    tree: Option<Tree<'a>>,
+    // replaces the filters of the main view when zoomed in
+    subtree: Option<Subtree<'a>>,
+    // false for `--no-color` and `NO_COLOR`
+    colors: bool,
+    theme: args::Theme,
 }
 
 impl<'a> App<'a> {
     fn new(packages: &'a BTreeMap<String, PackageDesc>) -> Self {
-        let mut dependants: BTreeMap<&str, BTreeSet<&str>> = Default::default();
-        for (name, package) in packages.iter() {
-            for dep in package.dependencies.iter().map(|dep| dep.as_str()).chain(
-                package
-                    .optional_dependencies
-                    .iter()
-                    .map(|dep| dep.name.as_str()),
-            ) {
-                // don't insert dependencies that aren't installed
-                if packages.contains_key(dep) {
-                    dependants.entry(dep).or_default().insert(name.as_str());
-                }
-            }
-        }
+        let providers = leptohadron::dependencies::providers(packages);
+        let dependants = leptohadron::dependencies::dependants(packages, &providers);
+        let required_dependants =
+            leptohadron::dependencies::required_dependants(packages, &providers);
         let left = Column {
-            title: "Dependants",
+            title: "Dependants".to_string(),
             ..Default::default()
         };
         let right = Column {
-            title: "Dependencies",
+            title: "Dependencies".to_string(),
             ..Default::default()
         };
         let mut center = Column {
-            title: "All",
+            title: "All".to_string(),
             is_active: true,
             sort_criteria: SortCritera::NameAsc,
             packages: packages.values().collect(),
-            list_state: Default::default(),
+            ..Default::default()
         };
-        center.sort_criteria.sort(center.packages.as_mut_slice());
+        center
+            .sort_criteria
+            .sort(center.packages.as_mut_slice(), &dependants);
         center.after_packages_change(None);
+        let defaults = Preferences::default();
         let mut self_ = Self {
             packages,
+            providers,
             dependants,
+            required_dependants,
             columns: [left, center, right],
             active_column: 1,
-            show_help: true,
-            filter: Default::default(),
-            searching: false,
+            show_help: defaults.show_help,
+            help_fits: true,
+            filters: defaults.filters,
+            sorted_packages: None,
+            total_size: packages.values().filter_map(|package| package.size).sum(),
+            explicit_count: packages
+                .values()
+                .filter(|package| matches!(package.reason, Reason::Explicit))
+                .count(),
+            scroll_step: 1,
+            load_time: Default::default(),
+            skipped: 0,
+            message: None,
+            log: Vec::new(),
+            binary_units: defaults.binary_units,
+            total_size_cache: Default::default(),
+            backend: Box::new(Pacman {
+                dbpath: args::DEFAULT_DBPATH.to_string(),
+            }),
+            show_files: defaults.show_files,
+            details_layout: defaults.details_layout,
+            files_cache: Default::default(),
+            separate_optional: defaults.separate_optional,
+            removal_mode: defaults.removal_mode,
+            prompt: None,
             search: String::new(),
+            case_sensitive: defaults.case_sensitive,
+            search_mode: defaults.search_mode,
+            search_scope: defaults.search_scope,
+            matcher: None,
+            filter_matcher: None,
+            reload: false,
+            seek_prefix: String::new(),
+            seek_time: None,
+            back: Vec::new(),
+            forward: Vec::new(),
+            peeks: Vec::new(),
+            trail: Vec::new(),
+            keymap: Keymap::default(),
+            confirm_quit: false,
+            relative_dates: true,
+            per_column_sort: false,
+            sort_criteria: SortCritera::NameAsc,
+            page_size: None,
+            confirming_quit: false,
+            interrupted: false,
+            tree: None,
+            subtree: None,
+            colors: true,
+            theme: Default::default(),
+            prompt_error: None,
+            input: String::new(),
         };
-        self_.apply_center_filter(Filter::Explicit);
+        self_.apply_center_filter();
         self_.update_sides(self_.columns[1].selected());
         self_
     }
 
     fn draw_help(&self, frame: &mut Frame<impl Backend>, area: Rect) {
-        let first_row_len = HELP.iter().map(|row| row.0.len()).max().unwrap();
+        let rows = help_rows(&self.keymap);
+        let first_row_len = rows.iter().map(|row| row.0.len()).max().unwrap();
         let constraints = &[
             Constraint::Length(first_row_len as u16),
             Constraint::Ratio(1, 1),
         ];
-        let help = Table::new(HELP.iter().map(|row| Row::new(vec![row.0, row.1])))
+        let help = Table::new(rows.iter().map(|row| Row::new(vec![row.0.as_str(), row.1])))
             .block(
                 Block::default()
                     .title("Help")
@@ -271,20 +982,347 @@ impl<'a> App<'a> {
         frame.render_widget(help, area);
     }
 
-    fn draw_search(&self, frame: &mut Frame<impl Backend>, area: Rect) {
-        let text = format!("/{}", self.search);
+    fn draw_tree(&mut self, frame: &mut Frame<impl Backend>, area: Rect) {
+        // Unwrap because the tree is only drawn when it is shown.
+        let tree = self.tree.as_mut().unwrap();
+        let block = Block::default()
+            .title(format!("Dependency tree of {}", tree.root.name))
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Thick);
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)])
+            .split(inner);
+        let items: Vec<ListItem> = tree
+            .rows
+            .iter()
+            .map(|row| {
+                let marker = match (row.cycle, row.has_dependencies) {
+                    (false, true) if tree.expanded.contains(&row.path) => "▾ ",
+                    (false, true) => "▸ ",
+                    _ => "  ",
+                };
+                let indent = "  ".repeat(row.path.len() - 1);
+                let cycle = match row.cycle {
+                    true => " (cycle)",
+                    false => "",
+                };
+                ListItem::new(format!("{indent}{marker}{}{cycle}", row.package.name))
+            })
+            .collect();
+        let list = List::new(items).highlight_style(Style::default().add_modifier(Modifier::BOLD));
+        frame.render_stateful_widget(list, chunks[0], &mut tree.list_state);
+        tree.height = chunks[0].height;
+        // Unwrap because of the invariant.
+        let selected = tree.rows[tree.list_state.selected().unwrap()].package;
+        self.draw_details(frame, chunks[1], Some(selected));
+    }
+
+    fn draw_prompt(&self, frame: &mut Frame<impl Backend>, area: Rect, prompt: Prompt) {
+        let settings = format!(
+            "[{}, {}, {}]",
+            self.search_mode.name(),
+            match self.case_sensitive {
+                true => "match case",
+                false => "ignore case",
+            },
+            self.search_scope.name(),
+        );
+        let text = match prompt {
+            Prompt::Search => format!("{settings} /{}", self.search),
+            Prompt::Filter => format!("{settings} |{}", self.input),
+            Prompt::Group => format!("group: {}", self.input),
+            Prompt::MinSize => format!("minimum size: {}", self.input),
+            Prompt::Export(format) => format!(
+                "export main view as {} to: {}",
+                match format {
+                    ExportFormat::Json => "JSON",
+                    ExportFormat::Csv => "CSV",
+                },
+                self.input
+            ),
+        };
+        let text = match &self.prompt_error {
+            Some(error) => format!("{text}  ({error})"),
+            None => text,
+        };
+        let paragraph = Paragraph::new(text);
+        frame.render_widget(paragraph, area);
+    }
+
+    fn draw_details(
+        &self,
+        frame: &mut Frame<impl Backend>,
+        area: Rect,
+        selected: Option<&PackageDesc>,
+    ) {
+        let mut text: Vec<Spans> = Default::default();
+        if let (Some(selected), true) = (selected, self.show_files) {
+            let style = Style::default().add_modifier(Modifier::UNDERLINED);
+            text.push(Spans(vec![Span::styled("files", style), ":".into()]));
+            match &*self.files(selected) {
+                // more can't be seen anyway
+                Ok(files) => text.extend(
+                    files
+                        .iter()
+                        .take(area.height as usize)
+                        .map(|file| Spans::from(file.clone())),
+                ),
+                Err(err) => text.push(err.clone().into()),
+            }
+        } else if let Some(selected) = selected {
+            let style = Style::default().add_modifier(Modifier::UNDERLINED);
+            // first so that it is seen even when the details are cut off
+            if is_orphan(selected, &self.dependants) {
+                text.push(Spans::from(Span::styled(
+                    "⚠ orphan (not required by any package)",
+                    Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+                )));
+            }
+            let mut fields = vec![
+                ("name", selected.name.clone()),
+                ("version", selected.version.clone()),
+                ("reason", format!("{:?}", selected.reason)),
+            ];
+            if let Reason::Dependency = selected.reason {
+                let chain = match self.explicit_chain(selected) {
+                    Some(chain) => chain.join(" → "),
+                    None => "no explicitly installed package".to_string(),
+                };
+                fields.push(("pulled in by", chain));
+            }
+            fields.extend([(
+                "size",
+                format!(
+                    "{}{} ({} with dependencies)",
+                    humansize::SizeFormatter::new(selected.size.unwrap_or(0), self.units()),
+                    share_of_total(selected.size.unwrap_or(0), self.total_size),
+                    humansize::SizeFormatter::new(self.total_size(selected), self.units()),
+                ),
+            )]);
+            if !selected.optional_dependencies.is_empty() {
+                fields.push((
+                    "optional deps",
+                    format!(
+                        "{}/{} installed",
+                        self.installed_optional_dependencies(selected),
+                        selected.optional_dependencies.len()
+                    ),
+                ));
+            }
+            fields.extend([
+                (
+                    "installed",
+                    match selected.install_date {
+                        Some(date) if self.relative_dates => format!(
+                            "{} ({})",
+                            format_age(date, chrono::Local::now().timestamp()),
+                            format_date(date)
+                        ),
+                        Some(date) => format_date(date),
+                        None => "unknown".to_string(),
+                    },
+                ),
+                (
+                    "built",
+                    selected
+                        .build_date
+                        .map(format_date)
+                        .unwrap_or_else(|| "unknown".to_string()),
+                ),
+            ]);
+            if let Some(arch) = &selected.arch {
+                fields.push(("arch", arch.clone()));
+            }
+            if let Some(packager) = &selected.packager {
+                fields.push(("packager", packager.clone()));
+            }
+            // align the values
+            let width = fields.iter().map(|(field, _)| field.len()).max().unwrap();
+            for (field, value) in fields {
+                text.push(Spans(vec![
+                    Span::styled(field, style),
+                    format!(":{:padding$} {value}", "", padding = width - field.len()).into(),
+                ]));
+            }
+            for (title, value, searched) in [
+                (
+                    "description",
+                    selected
+                        .description
+                        .as_deref()
+                        .unwrap_or("(no description)"),
+                    self.search_scope.includes_description() && selected.description.is_some(),
+                ),
+                (
+                    "url",
+                    selected.url.as_deref().unwrap_or("(no url)"),
+                    self.search_scope.includes_url() && selected.url.is_some(),
+                ),
+            ] {
+                let mut title = vec![Span::styled(title, style), ":".into()];
+                if searched && self.search_matches(value) {
+                    title.push(Span::styled(
+                        " matches search",
+                        Style::default().add_modifier(Modifier::REVERSED),
+                    ));
+                }
+                text.push("".into());
+                text.push(Spans(title));
+                text.push(value.into());
+            }
+            if !selected.dependencies.is_empty() {
+                text.push("".into());
+                text.push(Spans(vec![Span::styled("dependencies", style), ":".into()]));
+                for dep in &selected.dependencies {
+                    // dependencies that aren't installed are dimmed like optional ones
+                    let installed =
+                        !resolve_dependency(self.packages, &self.providers, &dep.name).is_empty();
+                    let style = match installed {
+                        true => Style::default(),
+                        false => Style::default().add_modifier(Modifier::DIM),
+                    };
+                    let mut line = vec![Span::styled(dep.name.as_str(), style)];
+                    if let Some(constraint) = &dep.constraint {
+                        line.push(Span::styled(format!(" {constraint}"), style));
+                    }
+                    text.push(Spans(line));
+                }
+            }
+            if !selected.optional_dependencies.is_empty() {
+                text.push("".into());
+                text.push(Spans(vec![
+                    Span::styled("optional dependencies", style),
+                    ":".into(),
+                ]));
+                for dep in &selected.optional_dependencies {
+                    // like `pacman -Qi`
+                    let installed =
+                        !resolve_dependency(self.packages, &self.providers, &dep.name).is_empty();
+                    let (marker, style) = match installed {
+                        true => ("✓ ", Style::default()),
+                        false => ("  ", Style::default().add_modifier(Modifier::DIM)),
+                    };
+                    let mut line = vec![marker.into(), Span::styled(dep.name.as_str(), style)];
+                    if let Some(description) = &dep.description {
+                        line.push(Span::styled(format!(": {description}"), style));
+                    }
+                    text.push(Spans(line));
+                }
+            }
+            for (title, entries) in [
+                ("conflicts", &selected.conflicts),
+                ("replaces", &selected.replaces),
+                ("groups", &selected.groups),
+                ("license", &selected.license),
+            ] {
+                if entries.is_empty() {
+                    continue;
+                }
+                text.push("".into());
+                text.push(Spans(vec![Span::styled(title, style), ":".into()]));
+                text.push(entries.join(" ").into());
+            }
+        }
+        let paragraph = Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::TOP));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn draw_status(&self, frame: &mut Frame<impl Backend>, area: Rect) {
+        let mut text = format!(
+            "{} packages ({} explicit, {} dependencies), {}, loaded in {} ms",
+            self.packages.len(),
+            self.explicit_count,
+            self.packages.len() - self.explicit_count,
+            humansize::SizeFormatter::new(self.total_size, self.units()),
+            self.load_time.as_millis(),
+        );
+        if self.skipped > 0 {
+            text.push_str(&format!(
+                ", skipped {} broken packages, see output after quitting",
+                self.skipped
+            ));
+        }
+        if let Some(message) = &self.message {
+            text = format!("{message} | {text}");
+        }
         let paragraph = Paragraph::new(text);
         frame.render_widget(paragraph, area);
     }
 
+    // Like `firefox › nss › nspr`, cut off at the start when it is too long so that the current
+    // package stays visible.
+    fn draw_trail(&self, frame: &mut Frame<impl Backend>, area: Rect) {
+        let names: Vec<&str> = self
+            .trail
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect();
+        let text = names.join(" › ");
+        let width = area.width as usize;
+        let count = text.chars().count();
+        let text = match count > width {
+            true => {
+                let kept: String = text.chars().skip(count - width + 1).collect();
+                format!("…{kept}")
+            }
+            false => text,
+        };
+        frame.render_widget(Paragraph::new(text), area);
+    }
+
     fn draw(&mut self, frame: &mut Frame<impl Backend>) {
-        let area = if self.show_help {
+        self.draw_views(frame);
+        if !self.colors {
+            frame.render_widget(StripColors, frame.size());
+        }
+    }
+
+    // Search matches are reversed and colored so that they stand out from the selection.
+    fn match_style(&self) -> Style {
+        let color = match self.theme {
+            args::Theme::Dark => Color::Yellow,
+            args::Theme::Light => Color::Blue,
+        };
+        Style::default().fg(color).add_modifier(Modifier::REVERSED)
+    }
+
+    // Packages installed as dependencies are grayed so that explicitly installed ones stand out.
+    fn dependency_style(&self) -> Style {
+        let color = match self.theme {
+            args::Theme::Dark => Color::DarkGray,
+            args::Theme::Light => Color::Gray,
+        };
+        Style::default().fg(color)
+    }
+
+    fn draw_views(&mut self, frame: &mut Frame<impl Backend>) {
+        let size = frame.size();
+        if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+            self.help_fits = false;
+            let paragraph = Paragraph::new("terminal too small")
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true });
+            let area = Rect {
+                y: size.y + size.height / 2,
+                height: size.height - size.height / 2,
+                ..size
+            };
+            frame.render_widget(paragraph, area);
+            return;
+        }
+        let help_height = (HELP.len() + 4) as u16;
+        // the status bar takes another line
+        self.help_fits = frame.size().height > help_height + MIN_LIST_HEIGHT;
+        let area = if self.show_help && self.help_fits {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Length((HELP.len() + 4) as u16),
-                    Constraint::Min(0),
-                ])
+                .constraints([Constraint::Length(help_height), Constraint::Min(0)])
                 .split(frame.size());
             self.draw_help(frame, chunks[0]);
             chunks[1]
@@ -292,24 +1330,94 @@ impl<'a> App<'a> {
             frame.size()
         };
 
-        let area = if self.searching {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(area);
+        self.draw_status(frame, chunks[1]);
+        let area = chunks[0];
+
+        let area = if let Some(prompt) = self.prompt {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([Constraint::Min(0), Constraint::Length(1)])
                 .split(area);
-            self.draw_search(frame, chunks[1]);
+            self.draw_prompt(frame, chunks[1], prompt);
             chunks[0]
         } else {
             area
         };
 
+        let area = match self.trail.len() >= 2 {
+            true => {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(1), Constraint::Min(0)])
+                    .split(area);
+                self.draw_trail(frame, chunks[0]);
+                chunks[1]
+            }
+            false => area,
+        };
+
+        // explain instead of showing three empty lists, for example when the path is wrong
+        if self.packages.is_empty() {
+            let text = format!("No packages found at {}", self.backend.location());
+            let paragraph = Paragraph::new(text)
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true });
+            let area = Rect {
+                y: area.y + area.height / 2,
+                height: area.height - area.height / 2,
+                ..area
+            };
+            frame.render_widget(paragraph, area);
+            return;
+        }
+
+        if self.tree.is_some() {
+            self.draw_tree(frame, area);
+            return;
+        }
+
+        let (area, bottom) = match self.details_layout {
+            DetailsLayout::Bottom => {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)])
+                    .split(area);
+                (chunks[0], Some(chunks[1]))
+            }
+            DetailsLayout::Columns | DetailsLayout::Hidden => (area, None),
+        };
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Ratio(1, 3); 3])
             .split(area);
 
-        for (column, chunk) in self.columns.iter_mut().zip(chunks) {
-            column.render(frame, chunk);
+        let match_style = self.match_style();
+        let dependency_style = self.dependency_style();
+        let details = self.details_layout == DetailsLayout::Columns;
+        // not `active_matcher` because that would borrow all of self
+        let search = self.matcher.as_ref().filter(|matcher| {
+            !self.search.is_empty()
+                && matcher.is_for(self.search.as_str(), self.search_mode, self.case_sensitive)
+        });
+        let detail_areas: Vec<Option<Rect>> = self
+            .columns
+            .iter_mut()
+            .zip(chunks)
+            .map(|(column, chunk)| {
+                column.render(frame, chunk, search, match_style, dependency_style, details)
+            })
+            .collect();
+        for (column, area) in self.columns.iter().zip(detail_areas) {
+            if let Some(area) = area {
+                self.draw_details(frame, area, column.selected());
+            }
+        }
+        if let Some(area) = bottom {
+            self.draw_details(frame, area, self.columns[self.active_column].selected());
         }
     }
 
@@ -317,130 +1425,734 @@ impl<'a> App<'a> {
     fn event(&mut self, event: Event) -> bool {
         let key = match event {
             Event::Key(key) => key,
+            Event::Mouse(mouse) => {
+                self.mouse_event(mouse);
+                self.update_trail();
+                return false;
+            }
             _ => return false,
         };
+        self.message = None;
+        // quits regardless of the keymap and while entering text
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.interrupted = true;
+            return true;
+        }
+        if std::mem::take(&mut self.confirming_quit) {
+            return key.code == KeyCode::Char('y');
+        }
         let mut list_selection_change = false;
         match key.code {
-            KeyCode::Char(char) if self.searching => self.search.push(char),
-            KeyCode::Backspace if self.searching => {
-                self.search.pop();
+            KeyCode::Char(char) if self.prompt.is_some() => {
+                self.prompt_text().unwrap().push(char);
+                self.after_prompt_edit();
             }
-            KeyCode::Char('/') => {
-                self.searching = true;
-                self.search.clear();
+            KeyCode::Backspace if self.prompt.is_some() => {
+                self.prompt_text().unwrap().pop();
+                self.after_prompt_edit();
             }
-            KeyCode::Esc if self.searching => {
-                self.searching = false;
-                self.search.clear();
-            }
-            KeyCode::Enter if self.searching => {
-                self.searching = false;
-                self.active_column = 1;
-                list_selection_change = self.search(SearchDirection::Down);
-            }
-            KeyCode::Char('n') => {
-                self.active_column = 1;
-                list_selection_change = self.search(SearchDirection::Down);
-            }
-            KeyCode::Char('N') => {
-                self.active_column = 1;
-                list_selection_change = self.search(SearchDirection::Up)
-            }
-
-            KeyCode::Char('q' | 'c') => return true,
-
-            KeyCode::Left => self.change_active_column(self.active_column.saturating_sub(1)),
-            KeyCode::Right => self.change_active_column((self.active_column + 1).min(2)),
-            KeyCode::Up => {
-                list_selection_change = self.columns[self.active_column].change(-1);
-            }
-            KeyCode::PageUp => {
-                list_selection_change = self.columns[self.active_column].change(-10);
-            }
-            KeyCode::Down => {
-                list_selection_change = self.columns[self.active_column].change(1);
-            }
-            KeyCode::PageDown => {
-                list_selection_change = self.columns[self.active_column].change(10);
-            }
-
-            KeyCode::Char('1') => {
-                let c = self.columns.get_mut(self.active_column).unwrap();
-                if !c.packages.is_empty() {
-                    let old = c.list_state.selected().unwrap();
-                    let new = 0;
-                    c.list_state.select(Some(new));
-                    list_selection_change = self.active_column == 1 && old != new;
+            KeyCode::Esc if self.prompt.is_some() => {
+                self.prompt_text().unwrap().clear();
+                if let Some(Prompt::Filter) = self.prompt.take() {
+                    self.set_filter(Filter::Search(String::new()), false);
                 }
+                self.prompt_error = None;
             }
-            KeyCode::Char('0') => {
-                let c = self.columns.get_mut(self.active_column).unwrap();
-                if !c.packages.is_empty() {
-                    let old = c.list_state.selected().unwrap();
-                    let new = c.packages.len() - 1;
-                    c.list_state.select(Some(new));
-                    list_selection_change = self.active_column == 1 && old != new;
+            KeyCode::Esc if self.subtree.is_some() => self.toggle_subtree(),
+            KeyCode::Enter if self.prompt.is_some() => match self.prompt.take().unwrap() {
+                Prompt::Search => match self.update_matcher() {
+                    Ok(()) => list_selection_change = self.search(SearchDirection::Down),
+                    // keep the prompt open so the term can be fixed
+                    Err(err) => {
+                        // Unwrap because the error message is never empty.
+                        self.prompt_error = Some(err.to_string().lines().last().unwrap().into());
+                        self.prompt = Some(Prompt::Search);
+                    }
+                },
+                // the main view was already narrowed while typing
+                Prompt::Filter => {
+                    if let Err(err) =
+                        Matcher::new(&self.input, self.search_mode, self.case_sensitive)
+                    {
+                        // Unwrap because the error message is never empty.
+                        self.prompt_error = Some(err.to_string().lines().last().unwrap().into());
+                        self.prompt = Some(Prompt::Filter);
+                    }
+                }
+                Prompt::Group => {
+                    let group = std::mem::take(&mut self.input);
+                    let enabled = !group.is_empty();
+                    self.set_filter(Filter::Group(group), enabled);
+                }
+                Prompt::MinSize => {
+                    let filter = match self.input.trim() {
+                        "" => Some((Filter::MinSize(0), false)),
+                        size => parse_size(size).map(|size| (Filter::MinSize(size), true)),
+                    };
+                    match filter {
+                        Some((filter, enabled)) => self.set_filter(filter, enabled),
+                        None => {
+                            self.prompt_error = Some("invalid size".to_string());
+                            self.prompt = Some(Prompt::MinSize);
+                        }
+                    }
+                }
+                Prompt::Export(format) => match self.export(self.input.trim(), format) {
+                    Ok(()) => {
+                        self.message = Some(format!(
+                            "exported {} packages to {}",
+                            self.columns[1].packages.len(),
+                            self.input.trim()
+                        ));
+                    }
+                    Err(err) => {
+                        self.log.push(format!("failed to export: {err:#}"));
+                        self.prompt_error = Some(format!("{err:#}"));
+                        self.prompt = Some(Prompt::Export(format));
+                    }
+                },
+            },
+            code => match self.keymap.action(code) {
+                Some(
+                    action @ (Action::MoveUp
+                    | Action::MoveDown
+                    | Action::PageUp
+                    | Action::PageDown
+                    | Action::Start
+                    | Action::End
+                    | Action::Focus
+                    | Action::ToggleExpand),
+                ) if self.tree.is_some() => self.tree_event(action),
+                Some(Action::Search) => {
+                    self.prompt = Some(Prompt::Search);
+                    self.search.clear();
+                }
+                Some(Action::Filter) => {
+                    self.prompt = Some(Prompt::Filter);
+                    self.input.clear();
+                    self.set_filter(Filter::Search(String::new()), false);
+                }
+                Some(Action::Group) => {
+                    self.prompt = Some(Prompt::Group);
+                    self.input.clear();
+                }
+                Some(Action::MinSize) => {
+                    self.prompt = Some(Prompt::MinSize);
+                    self.input.clear();
+                }
+                Some(Action::Export) => {
+                    self.prompt = Some(Prompt::Export(ExportFormat::Json));
+                    self.input.clear();
+                }
+                Some(Action::ExportCsv) => {
+                    self.prompt = Some(Prompt::Export(ExportFormat::Csv));
+                    self.input.clear();
+                }
+                Some(Action::NextMatch) => {
+                    list_selection_change = self.search(SearchDirection::Down)
+                }
+                Some(Action::PreviousMatch) => {
+                    list_selection_change = self.search(SearchDirection::Up)
                 }
-            }
-
-            KeyCode::Enter if self.active_column != 1 => {
-                self.change_center_package();
-            }
-
-            KeyCode::Char('s') => {
-                let c = &mut self.columns[self.active_column];
-                let selected = c.selected();
-                c.sort_criteria = match c.sort_criteria {
-                    SortCritera::NameAsc => SortCritera::SizeDesc,
-                    SortCritera::SizeDesc => SortCritera::NameAsc,
-                };
-                c.sort_criteria.sort(c.packages.as_mut_slice());
-                c.after_packages_change(selected);
-            }
 
-            KeyCode::Char('e') => {
-                let filter = match self.filter {
-                    Filter::All => Filter::Explicit,
-                    Filter::Explicit => Filter::All,
-                };
-                self.apply_center_filter(filter);
-            }
+                Some(Action::ToggleCase) => {
+                    self.case_sensitive = !self.case_sensitive;
+                    self.after_search_settings_change();
+                }
+                Some(Action::CycleSearchMode) => {
+                    self.search_mode = self.search_mode.next();
+                    self.after_search_settings_change();
+                }
+                Some(Action::CycleSearchScope) => {
+                    self.search_scope = self.search_scope.next();
+                    self.clear_search_matches();
+                    self.after_search_settings_change();
+                }
+
+                Some(Action::Quit) if self.confirm_quit => {
+                    self.confirming_quit = true;
+                    self.message = Some("Quit? (y/n)".to_string());
+                }
+                Some(Action::Quit) => return true,
+
+                Some(Action::MoveLeft) => {
+                    self.change_active_column(self.active_column.saturating_sub(1))
+                }
+                Some(Action::MoveRight) => {
+                    self.change_active_column((self.active_column + 1).min(2))
+                }
+                Some(Action::NextColumn) => self.change_active_column((self.active_column + 1) % 3),
+                Some(Action::PreviousColumn) => {
+                    self.change_active_column((self.active_column + 2) % 3)
+                }
+                Some(Action::MoveUp) => {
+                    list_selection_change = self.columns[self.active_column].change(-1);
+                }
+                Some(Action::PageUp) => {
+                    let page = self.page_size(self.columns[self.active_column].list_area.height);
+                    list_selection_change = self.columns[self.active_column].change(-page);
+                }
+                Some(Action::MoveDown) => {
+                    list_selection_change = self.columns[self.active_column].change(1);
+                }
+                Some(Action::PageDown) => {
+                    let page = self.page_size(self.columns[self.active_column].list_area.height);
+                    list_selection_change = self.columns[self.active_column].change(page);
+                }
+
+                Some(Action::Start) => {
+                    let c = self.columns.get_mut(self.active_column).unwrap();
+                    if !c.packages.is_empty() {
+                        let old = c.list_state.selected().unwrap();
+                        let new = 0;
+                        c.list_state.select(Some(new));
+                        list_selection_change = self.active_column == 1 && old != new;
+                    }
+                }
+                Some(Action::End) => {
+                    let c = self.columns.get_mut(self.active_column).unwrap();
+                    if !c.packages.is_empty() {
+                        let old = c.list_state.selected().unwrap();
+                        let new = c.packages.len() - 1;
+                        c.list_state.select(Some(new));
+                        list_selection_change = self.active_column == 1 && old != new;
+                    }
+                }
+
+                Some(Action::Focus) if self.active_column != 1 => {
+                    self.change_center_package();
+                }
+                Some(Action::Peek) if self.active_column != 1 => self.peek(),
+                Some(Action::ReturnFromPeek) => self.return_from_peek(),
+                Some(Action::Back) => self.navigate_history(true),
+                Some(Action::Forward) => self.navigate_history(false),
+
+                Some(Action::CycleSort) => {
+                    let (sort_criteria, columns) = match self.per_column_sort {
+                        true => (
+                            self.columns[self.active_column].sort_criteria.next(),
+                            self.active_column..self.active_column + 1,
+                        ),
+                        false => {
+                            self.sort_criteria = self.sort_criteria.next();
+                            (self.sort_criteria, 0..3)
+                        }
+                    };
+                    for column in columns {
+                        self.columns[column].sort_criteria = sort_criteria;
+                        match column {
+                            // filtering again keeps the order of a fuzzy search
+                            1 => self.apply_center_filter(),
+                            _ => {
+                                let c = &mut self.columns[column];
+                                let selected = c.selected();
+                                c.sort(&self.dependants);
+                                c.after_packages_change(selected);
+                            }
+                        }
+                    }
+                }
+
+                Some(Action::ToggleExplicit) => {
+                    let enabled = !self.filters.contains(&Filter::Explicit);
+                    self.set_filter(Filter::Explicit, enabled);
+                }
+
+                Some(Action::ToggleOrphans) => {
+                    let enabled = !self.filters.contains(&Filter::Orphans);
+                    self.set_filter(Filter::Orphans, enabled);
+                }
+                Some(Action::ToggleLeaves) => {
+                    let enabled = !self.filters.contains(&Filter::Leaves);
+                    self.set_filter(Filter::Leaves, enabled);
+                }
+
+                Some(Action::ToggleRemoval) => {
+                    self.removal_mode = !self.removal_mode;
+                    self.update_right_title();
+                    self.update_sides(self.columns[1].selected());
+                }
+
+                Some(Action::Reload) => {
+                    self.reload = true;
+                    return true;
+                }
+
+                Some(Action::ToggleOptional) => {
+                    self.separate_optional = !self.separate_optional;
+                    self.update_sides(self.columns[1].selected());
+                }
+
+                Some(Action::ToggleUnits) => {
+                    self.binary_units = !self.binary_units;
+                    // the title shows the minimum size
+                    self.apply_center_filter();
+                }
+
+                Some(Action::ToggleFiles) => self.show_files = !self.show_files,
+                Some(Action::CycleDetails) => self.details_layout = self.details_layout.next(),
+
+                Some(Action::OpenUrl) => {
+                    if let Some(package) = self.columns[self.active_column].selected() {
+                        self.message = Some(match &package.url {
+                            Some(url) => match open_url(url) {
+                                Ok(()) => format!("opened {url}"),
+                                Err(err) => {
+                                    let message = format!("failed to open {url}: {err:#}");
+                                    self.log.push(message.clone());
+                                    message
+                                }
+                            },
+                            None => format!("{} has no url", package.name),
+                        });
+                    }
+                }
+
+                Some(Action::ToggleTree) => self.toggle_tree(),
+                Some(Action::ToggleSubtree) => self.toggle_subtree(),
+
+                Some(Action::ToggleHelp) => {
+                    self.show_help = !self.show_help;
+                    if self.show_help && !self.help_fits {
+                        self.message = Some("the terminal is too short for the help".to_string());
+                    }
+                }
 
-            KeyCode::Char('?') => self.show_help = !self.show_help,
+                // keys without an action jump to a package, so keys with one never do
+                None => {
+                    if let KeyCode::Char(char) = key.code {
+                        list_selection_change = self.seek(char);
+                    }
+                }
 
-            _ => (),
+                _ => (),
+            },
         }
         if list_selection_change && self.active_column == 1 {
             let package = self.columns.get(1).unwrap().selected();
             self.update_sides(package);
         }
+        self.update_trail();
         false
     }
 
+    // Continues the trail if it ends at `from`, otherwise starts a new one.
+    fn extend_trail(&mut self, from: &'a PackageDesc, to: &'a PackageDesc) {
+        if !self
+            .trail
+            .last()
+            .is_some_and(|last| std::ptr::eq(*last, from))
+        {
+            self.trail = vec![from];
+        }
+        self.trail.push(to);
+    }
+
+    // The trail is cleared when the main package changes some other way, like searching or moving
+    // in the center list.
+    fn update_trail(&mut self) {
+        let current = self.columns[1].selected();
+        let follows = match (self.trail.last(), current) {
+            (Some(last), Some(current)) => std::ptr::eq(*last, current),
+            _ => false,
+        };
+        if !follows || self.trail.len() < 2 {
+            self.trail.clear();
+        }
+    }
+
+    fn after_prompt_edit(&mut self) {
+        self.prompt_error = None;
+        if let Some(Prompt::Search) = self.prompt {
+            // so that matches are highlighted while typing, errors are reported on enter
+            let _ = self.update_matcher();
+        }
+        if let Some(Prompt::Filter) = self.prompt {
+            let enabled = !self.input.is_empty();
+            self.set_filter(Filter::Search(self.input.clone()), enabled);
+        }
+    }
+
+    // The main view is narrowed with the new settings.
+    fn after_search_settings_change(&mut self) {
+        if self
+            .filters
+            .iter()
+            .any(|filter| matches!(filter, Filter::Search(_)))
+        {
+            self.apply_center_filter();
+            self.update_sides(self.columns[1].selected());
+        }
+    }
+
+    // Writes the packages of the main view as a JSON array or CSV table.
+    fn export(&self, path: &str, format: ExportFormat) -> Result<()> {
+        let contents = match format {
+            ExportFormat::Json => {
+                serde_json::to_string_pretty(&self.columns[1].packages).context("serialize")?
+            }
+            ExportFormat::Csv => to_csv(&self.columns[1].packages),
+        };
+        std::fs::write(path, contents).with_context(|| format!("write {path}"))
+    }
+
+    // text the user is entering for the active prompt
+    fn prompt_text(&mut self) -> Option<&mut String> {
+        match self.prompt? {
+            Prompt::Search => Some(&mut self.search),
+            Prompt::Filter | Prompt::Group | Prompt::MinSize | Prompt::Export(_) => {
+                Some(&mut self.input)
+            }
+        }
+    }
+
+    fn mouse_event(&mut self, mouse: MouseEvent) {
+        // the lists aren't visible
+        if self.tree.is_some() {
+            return;
+        }
+        let distance = match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => None,
+            MouseEventKind::ScrollUp => Some(-self.scroll_step),
+            MouseEventKind::ScrollDown => Some(self.scroll_step),
+            _ => return,
+        };
+        let column = match self
+            .columns
+            .iter()
+            .position(|c| contains(c.area, mouse.column, mouse.row))
+        {
+            Some(column) => column,
+            None => return,
+        };
+        // scrolling moves the list under the cursor without focusing it
+        if let Some(distance) = distance {
+            if self.columns[column].change(distance) && column == 1 {
+                self.update_sides(self.columns[1].selected());
+            }
+            return;
+        }
+        self.change_active_column(column);
+        let c = self.columns.get_mut(column).unwrap();
+        if let Some(index) = c.entry_at(mouse.column, mouse.row) {
+            let changed = c.list_state.selected() != Some(index);
+            c.list_state.select(Some(index));
+            if changed && column == 1 {
+                let package = c.selected();
+                self.update_sides(package);
+            }
+        }
+    }
+
+    fn update_right_title(&mut self) {
+        self.columns[2].title = match self.removal_mode {
+            true => "Would also remove",
+            false => "Dependencies",
+        }
+        .to_string();
+    }
+
+    fn settings(&self) -> Settings {
+        Settings {
+            main_package: self.columns[1]
+                .selected()
+                .map(|package| package.name.clone()),
+            active_column: self.active_column,
+            search: self.search.clone(),
+            preferences: self.preferences(),
+        }
+    }
+
+    fn restore(&mut self, settings: Settings) {
+        self.search = settings.search;
+        self.apply_preferences(settings.preferences);
+        // the package might have been removed
+        let main_package = settings
+            .main_package
+            .and_then(|name| self.packages.get(name.as_str()));
+        self.columns[1].after_packages_change(main_package);
+        self.update_sides(self.columns[1].selected());
+        self.change_active_column(settings.active_column);
+    }
+
+    // The main package for `--print-selection` after quitting.
+    fn picked(&self) -> Option<&'a str> {
+        match self.interrupted {
+            true => None,
+            false => self.columns[1]
+                .selected()
+                .map(|package| package.name.as_str()),
+        }
+    }
+
+    fn preferences(&self) -> Preferences {
+        Preferences {
+            sort_criteria: self.columns.each_ref().map(|c| c.sort_criteria),
+            show_help: self.show_help,
+            show_files: self.show_files,
+            details_layout: self.details_layout,
+            // not the filters while zoomed in
+            filters: match &self.subtree {
+                Some(subtree) => subtree.filters.clone(),
+                None => self.filters.clone(),
+            },
+            binary_units: self.binary_units,
+            separate_optional: self.separate_optional,
+            removal_mode: self.removal_mode,
+            case_sensitive: self.case_sensitive,
+            search_mode: self.search_mode,
+            search_scope: self.search_scope,
+        }
+    }
+
+    fn apply_preferences(&mut self, preferences: Preferences) {
+        let mut sort_criteria = preferences.sort_criteria;
+        self.sort_criteria = sort_criteria[1];
+        // saved while sorting per column
+        if !self.per_column_sort {
+            sort_criteria = [self.sort_criteria; 3];
+        }
+        for (c, sort_criteria) in self.columns.iter_mut().zip(sort_criteria) {
+            c.sort_criteria = sort_criteria;
+        }
+        self.show_help = preferences.show_help;
+        self.show_files = preferences.show_files;
+        self.details_layout = preferences.details_layout;
+        self.filters = preferences.filters;
+        self.binary_units = preferences.binary_units;
+        self.separate_optional = preferences.separate_optional;
+        self.removal_mode = preferences.removal_mode;
+        self.case_sensitive = preferences.case_sensitive;
+        self.search_mode = preferences.search_mode;
+        self.search_scope = preferences.search_scope;
+        self.update_right_title();
+        self.apply_center_filter();
+        self.update_sides(self.columns[1].selected());
+    }
+
     fn change_active_column(&mut self, new: usize) {
         self.columns.get_mut(self.active_column).unwrap().is_active = false;
         self.columns.get_mut(new).unwrap().is_active = true;
         self.active_column = new;
     }
 
-    fn apply_center_filter(&mut self, filter: Filter) {
-        self.filter = filter;
+    // Replaces the filter of the same kind, or removes it if not enabled. Also removes filters that
+    // can't be combined with it.
+    fn set_filter(&mut self, filter: Filter, enabled: bool) {
+        self.filters.retain(|filter_| {
+            std::mem::discriminant(filter_) != std::mem::discriminant(&filter)
+                && !(enabled && filter_.excludes(&filter))
+        });
+        if enabled {
+            self.filters.push(filter);
+        }
+        self.apply_center_filter();
+        self.update_sides(self.columns[1].selected());
+    }
+
+    fn passes_filters(&self, package: &PackageDesc) -> bool {
+        let in_subtree = match &self.subtree {
+            Some(subtree) => subtree.packages.contains(package.name.as_str()),
+            None => true,
+        };
+        let matches_search = self
+            .filter_matcher
+            .as_ref()
+            .is_none_or(|matcher| matcher.score_package(package, self.search_scope).is_some());
+        in_subtree
+            && matches_search
+            && self
+                .filters
+                .iter()
+                .all(|filter| filter.filter(package, &self.dependants))
+    }
+
+    fn apply_center_filter(&mut self) {
+        let sort_criteria = self.columns[1].sort_criteria;
+        let sorted = match self.sorted_packages.take() {
+            Some((sort_criteria_, sorted)) if sort_criteria_ == sort_criteria => sorted,
+            _ => {
+                let mut sorted: Vec<&'a PackageDesc> = self.packages.values().collect();
+                sort_criteria.sort(sorted.as_mut_slice(), &self.dependants);
+                sorted
+            }
+        };
+        // While typing a regex it is often invalid, which shows everything until it is valid again.
+        self.filter_matcher = self.filters.iter().find_map(|filter| match filter {
+            Filter::Search(term) => Matcher::new(term, self.search_mode, self.case_sensitive).ok(),
+            _ => None,
+        });
+        // filtering keeps the order
+        let mut packages: Vec<&'a PackageDesc> = sorted
+            .iter()
+            .copied()
+            .filter(|package| self.passes_filters(package))
+            .collect();
+        // best matches first like fzf, stable so that equal scores keep the sorting
+        if let (Some(matcher), SearchMode::Fuzzy) = (&self.filter_matcher, self.search_mode) {
+            packages.sort_by_cached_key(|package| {
+                std::cmp::Reverse(matcher.score_package(package, self.search_scope))
+            });
+        }
+        self.sorted_packages = Some((sort_criteria, sorted));
+        let units = self.units();
+        let all = match &self.subtree {
+            Some(subtree) => format!(
+                "Subtree of {}, {}",
+                subtree.root.name,
+                humansize::SizeFormatter::new(self.total_size(subtree.root), units)
+            ),
+            None => "All".to_string(),
+        };
+        // what the shown packages take up, packages without a size count as nothing
+        let size: u64 = packages
+            .iter()
+            .map(|package| package.size.unwrap_or(0))
+            .sum();
         let c = self.columns.get_mut(1).unwrap();
+        c.title = match self.filters.is_empty() {
+            true => all,
+            false => format!(
+                "{all} [{}] — {}",
+                self.filters
+                    .iter()
+                    .map(|filter| filter.name(units))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                humansize::SizeFormatter::new(size, units)
+            ),
+        };
         let selected = c.selected();
-        c.packages = self
-            .packages
-            .values()
-            .filter(|package| self.filter.filter(package))
-            .collect();
+        c.packages = packages;
         c.after_packages_change(selected);
-        if let Some(selected) = selected {
-            let pos = c
-                .packages
-                .iter()
-                .position(|desc| desc.name == selected.name);
-            if let Some(pos) = pos {
-                c.list_state.select(Some(pos));
+    }
+
+    // Zooms the main view in on the selected package and what it depends on, or back out to the
+    // previous filters.
+    fn toggle_subtree(&mut self) {
+        let root = match self.subtree.take() {
+            Some(subtree) => {
+                self.filters = subtree.filters;
+                Some(subtree.root)
+            }
+            None => {
+                let root = match self.columns[self.active_column].selected() {
+                    Some(package) => package,
+                    None => return,
+                };
+                self.subtree = Some(Subtree {
+                    root,
+                    packages: self
+                        .dependency_closure(root)
+                        .into_iter()
+                        .map(|package| package.name.as_str())
+                        .collect(),
+                    filters: std::mem::take(&mut self.filters),
+                });
+                Some(root)
+            }
+        };
+        self.apply_center_filter();
+        self.columns[1].after_packages_change(root);
+        self.change_active_column(1);
+        self.update_sides(self.columns[1].selected());
+    }
+
+    fn toggle_tree(&mut self) {
+        if self.tree.take().is_some() {
+            return;
+        }
+        let root = match self.columns[self.active_column].selected() {
+            Some(package) => package,
+            None => return,
+        };
+        let mut list_state = ListState::default();
+        list_state.select(Some(0));
+        self.tree = Some(Tree {
+            root,
+            expanded: BTreeSet::from([vec![root.name.as_str()]]),
+            rows: Vec::new(),
+            list_state,
+            height: 0,
+        });
+        self.update_tree();
+    }
+
+    // The configured page size or else the height of the list, 10 if it hasn't been drawn yet.
+    fn page_size(&self, height: u16) -> isize {
+        let height = (height > 0).then_some(height as usize);
+        self.page_size.or(height).unwrap_or(10) as isize
+    }
+
+    // Moves in the tree or expands the selected row.
+    fn tree_event(&mut self, action: Action) {
+        // Unwrap because this is only called when the tree is shown.
+        let page = self.page_size(self.tree.as_ref().unwrap().height) as usize;
+        let tree = self.tree.as_mut().unwrap();
+        let last = tree.rows.len() - 1;
+        let selected = tree.list_state.selected().unwrap();
+        let new = match action {
+            Action::MoveUp => selected.saturating_sub(1),
+            Action::MoveDown => (selected + 1).min(last),
+            Action::PageUp => selected.saturating_sub(page),
+            Action::PageDown => (selected + page).min(last),
+            Action::Start => 0,
+            Action::End => last,
+            _ => {
+                let row = &tree.rows[selected];
+                if !row.cycle && !tree.expanded.remove(&row.path) {
+                    tree.expanded.insert(row.path.clone());
+                }
+                self.update_tree();
+                return;
             }
+        };
+        tree.list_state.select(Some(new));
+    }
+
+    fn update_tree(&mut self) {
+        let mut tree = match self.tree.take() {
+            Some(tree) => tree,
+            None => return,
+        };
+        tree.rows.clear();
+        self.tree_rows(
+            tree.root,
+            vec![tree.root.name.as_str()],
+            &tree.expanded,
+            &mut tree.rows,
+        );
+        // rows above the selection don't change when expanding, so the selection stays on its row
+        let selected = tree.list_state.selected().unwrap().min(tree.rows.len() - 1);
+        tree.list_state.select(Some(selected));
+        self.tree = Some(tree);
+    }
+
+    // Appends the row for the package and the rows below it if it is expanded. The dependencies of
+    // a package that is its own ancestor are not listed again, so cycles end.
+    fn tree_rows(
+        &self,
+        package: &'a PackageDesc,
+        path: Vec<&'a str>,
+        expanded: &BTreeSet<Vec<&'a str>>,
+        rows: &mut Vec<TreeRow<'a>>,
+    ) {
+        let dependencies = self.resolved_dependencies(package);
+        let cycle = path[..path.len() - 1].contains(&package.name.as_str());
+        let expand = !cycle && expanded.contains(&path);
+        rows.push(TreeRow {
+            package,
+            path: path.clone(),
+            cycle,
+            has_dependencies: !dependencies.is_empty(),
+        });
+        if !expand {
+            return;
+        }
+        for dependency in dependencies {
+            let mut path = path.clone();
+            path.push(dependency);
+            self.tree_rows(&self.packages[dependency], path, expanded, rows);
         }
     }
 
@@ -449,8 +2161,142 @@ impl<'a> App<'a> {
             Some(package) => package,
             None => return,
         };
-        if let (Reason::Dependency, Filter::Explicit) = (package.reason, self.filter) {
-            self.apply_center_filter(Filter::All);
+        if let Some(previous) = self.columns[1].selected() {
+            if !std::ptr::eq(previous, package) {
+                self.back.push(previous);
+                self.forward.clear();
+                self.extend_trail(previous, package);
+            }
+        }
+        self.focus_package(package);
+    }
+
+    // Makes the selected package of the active side list the main package without leaving the side
+    // list, so that peeking can continue from its dependants or dependencies.
+    fn peek(&mut self) {
+        let peeked = match self.columns[self.active_column].selected() {
+            Some(package) => package,
+            None => return,
+        };
+        let previous = match self.columns[1].selected() {
+            Some(package) => package,
+            None => return,
+        };
+        self.peeks.push(Peek {
+            previous,
+            column: self.active_column,
+            peeked,
+            filters: self.filters.clone(),
+            subtree: self.subtree.clone(),
+        });
+        self.extend_trail(previous, peeked);
+        self.focus_package(peeked);
+    }
+
+    // Undoes the most recent peek, selecting the peeked package again in the side list.
+    fn return_from_peek(&mut self) {
+        let peek = match self.peeks.pop() {
+            Some(peek) => peek,
+            None => return,
+        };
+        self.trail.pop();
+        self.filters = peek.filters;
+        self.subtree = peek.subtree;
+        self.apply_center_filter();
+        self.focus_package(peek.previous);
+        self.columns[peek.column].after_packages_change(Some(peek.peeked));
+        self.change_active_column(peek.column);
+    }
+
+    fn seeking(&self) -> bool {
+        self.seek_time
+            .is_some_and(|time| time.elapsed() < SEEK_TIMEOUT)
+    }
+
+    // Adds the character to the prefix and selects the next package in the active column whose
+    // name starts with it. Returns whether the selection changed.
+    fn seek(&mut self, char: char) -> bool {
+        let extend = self.seeking();
+        if !extend {
+            self.seek_prefix.clear();
+        }
+        self.seek_prefix.extend(char.to_lowercase());
+        self.seek_time = Some(std::time::Instant::now());
+        let c = self.columns.get_mut(self.active_column).unwrap();
+        let current = match c.list_state.selected() {
+            Some(current) => current,
+            None => return false,
+        };
+        // a longer prefix can still match the current package
+        let start = match extend {
+            true => current,
+            false => current + 1,
+        };
+        let found = (start..c.packages.len()).chain(0..start).find(|i| {
+            c.packages[*i]
+                .name
+                .to_lowercase()
+                .starts_with(&self.seek_prefix)
+        });
+        match found {
+            Some(found) => {
+                c.list_state.select(Some(found));
+                found != current
+            }
+            None => false,
+        }
+    }
+
+    // Goes back to the previous main package if `back`, otherwise forward again.
+    fn navigate_history(&mut self, back: bool) {
+        let (from, to) = match back {
+            true => (&mut self.back, &mut self.forward),
+            false => (&mut self.forward, &mut self.back),
+        };
+        let package = match from.pop() {
+            Some(package) => package,
+            None => return,
+        };
+        let current = self.columns[1].selected();
+        if let Some(current) = current {
+            to.push(current);
+        }
+        match (back, current) {
+            (true, _) => {
+                self.trail.pop();
+            }
+            (false, Some(current)) => self.extend_trail(current, package),
+            (false, None) => (),
+        }
+        self.focus_package(package);
+    }
+
+    // Filters the package doesn't pass are removed so that it is in the main view.
+    fn focus_package(&mut self, package: &'a PackageDesc) {
+        if !self.passes_filters(package) {
+            let outside = self
+                .subtree
+                .take_if(|subtree| !subtree.packages.contains(package.name.as_str()));
+            if let Some(subtree) = outside {
+                self.filters = subtree.filters;
+            }
+            let passes: Vec<bool> = self
+                .filters
+                .iter()
+                .map(|filter| match filter {
+                    Filter::Search(term) => {
+                        Matcher::new(term, self.search_mode, self.case_sensitive)
+                            .ok()
+                            .is_none_or(|matcher| {
+                                matcher.score_package(package, self.search_scope).is_some()
+                            })
+                    }
+                    filter => filter.filter(package, &self.dependants),
+                })
+                .collect();
+            let mut passes = passes.into_iter();
+            self.filters.retain(|_| passes.next().unwrap());
+            self.apply_center_filter();
         }
         let c = self.columns.get_mut(1).unwrap();
         c.after_packages_change(Some(package));
@@ -464,12 +2310,30 @@ impl<'a> App<'a> {
                 for column in [0, 2] {
                     let c = self.columns.get_mut(column).unwrap();
                     c.packages.clear();
+                    c.dimmed.clear();
                     c.after_packages_change(None);
                 }
                 return;
             }
         };
-        for (column, mut packages) in [
+        // sorted after the others
+        let optional: BTreeSet<&'a str> = match self.separate_optional && !self.removal_mode {
+            true => {
+                let required =
+                    self.resolve_all(package.dependencies.iter().map(|dep| dep.name.as_str()));
+                self.resolve_all(
+                    package
+                        .optional_dependencies
+                        .iter()
+                        .map(|dep| dep.name.as_str()),
+                )
+                .difference(&required)
+                .copied()
+                .collect()
+            }
+            false => Default::default(),
+        };
+        for (column, packages) in [
             (
                 0,
                 self.dependants
@@ -481,88 +2345,2241 @@ impl<'a> App<'a> {
             ),
             (
                 2,
-                self.packages
-                    .get(package.name.as_str())
-                    .into_iter()
-                    .flat_map(|desc| {
-                        desc.dependencies.iter().map(|s| s.as_str()).chain(
-                            desc.optional_dependencies
-                                .iter()
-                                .map(|dep| dep.name.as_str()),
-                        )
-                    })
-                    .filter_map(|s| self.packages.get(s))
-                    .collect::<Vec<_>>(),
+                match self.removal_mode {
+                    true => self.removal_closure(package),
+                    false => self.resolved_dependencies(package),
+                }
+                .into_iter()
+                .filter_map(|s| self.packages.get(s))
+                .collect::<Vec<_>>(),
             ),
         ] {
             let c = self.columns.get_mut(column).unwrap();
-            c.sort_criteria.sort(packages.as_mut_slice());
+            c.dimmed = match column {
+                2 => optional.clone(),
+                _ => Default::default(),
+            };
             c.packages = packages;
+            c.sort(&self.dependants);
             c.after_packages_change(None);
         }
     }
 
-    // returns whether selection changed
-    fn search(&mut self, search_direction: SearchDirection) -> bool {
-        if self.search.is_empty() {
-            return false;
+    // Installed packages satisfying the dependencies and optional dependencies of the package.
+    fn resolved_dependencies(&self, package: &PackageDesc) -> BTreeSet<&'a str> {
+        self.resolve_all(
+            package
+                .dependencies
+                .iter()
+                .map(|dep| dep.name.as_str())
+                .chain(
+                    package
+                        .optional_dependencies
+                        .iter()
+                        .map(|dep| dep.name.as_str()),
+                ),
+        )
+    }
+
+    fn resolve_all<'b>(&self, dependencies: impl Iterator<Item = &'b str>) -> BTreeSet<&'a str> {
+        dependencies
+            .flat_map(|s| resolve_dependency(self.packages, &self.providers, s))
+            .collect()
+    }
+
+    // The packages that `pacman -Rs` would remove in addition to the package: dependencies that
+    // weren't explicitly installed and are only required by packages that are being removed.
+    // Optional dependencies are ignored like pacman does.
+    fn removal_closure(&self, package: &PackageDesc) -> BTreeSet<&'a str> {
+        let mut removed: BTreeSet<&'a str> = BTreeSet::new();
+        if let Some((name, _)) = self.packages.get_key_value(package.name.as_str()) {
+            removed.insert(name.as_str());
         }
-        let c = self.columns.get_mut(1).unwrap();
-        let index = match c.list_state.selected() {
-            Some(i) => i,
-            None => return false,
-        };
-        let before = c.packages.iter().enumerate().take(index);
-        let after = c.packages.iter().enumerate().skip(index + 1);
-        let mut iter = after.chain(before);
-        let condition =
-            |(_, package): &(_, &&PackageDesc)| package.name.contains(self.search.as_str());
-        let result = match search_direction {
-            SearchDirection::Down => iter.find(condition),
-            SearchDirection::Up => iter.rev().find(condition),
-        };
-        match result {
-            Some((index, _)) => {
-                c.list_state.select(Some(index));
-                true
+        loop {
+            let new: Vec<&'a str> = removed
+                .iter()
+                .filter_map(|name| self.packages.get(*name))
+                .flat_map(|package| {
+                    self.resolve_all(package.dependencies.iter().map(|dep| dep.name.as_str()))
+                })
+                .filter(|name| {
+                    !removed.contains(name)
+                        && matches!(self.packages[*name].reason, Reason::Dependency)
+                        && self
+                            .required_dependants
+                            .get(name)
+                            .into_iter()
+                            .flatten()
+                            .all(|dependant| removed.contains(dependant))
+                })
+                .collect();
+            if new.is_empty() {
+                break;
             }
-            None => false,
+            removed.extend(new);
         }
+        removed.remove(package.name.as_str());
+        removed
     }
-}
 
-fn main() -> Result<()> {
-    const PATH: &str = "/var/lib/pacman/local";
-    let packages: BTreeMap<String, PackageDesc> = installed_packages::from_directory(PATH)
-        .with_context(|| format!("failed to load installed packages from {PATH}"))?
-        .map(|desc| desc.map(|desc| (desc.name.clone(), desc)))
-        .collect::<Result<_>>()?;
-    let mut app = App::new(&packages);
+    // Reads the files of the package on first use.
+    fn files(&self, package: &PackageDesc) -> std::cell::Ref<'_, Result<Vec<String>, String>> {
+        if !self.files_cache.borrow().contains_key(&package.name) {
+            let files = self
+                .backend
+                .files(package)
+                .map_err(|err| format!("{err:#}"));
+            self.files_cache
+                .borrow_mut()
+                .insert(package.name.clone(), files);
+        }
+        std::cell::Ref::map(self.files_cache.borrow(), |cache| &cache[&package.name])
+    }
 
-    let mut stdout = std::io::stdout();
-    crossterm::terminal::enable_raw_mode().context("enable_raw_mode")?;
-    crossterm::execute!(stdout, EnterAlternateScreen).context("EnterAlternateScreen")?;
-    let backend = tui::backend::CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend).context("Terminal::new")?;
+    fn units(&self) -> humansize::FormatSizeOptions {
+        match self.binary_units {
+            true => humansize::BINARY,
+            false => humansize::DECIMAL,
+        }
+    }
 
-    let result = loop {
-        match terminal.draw(|frame| app.draw(frame)) {
-            Ok(_) => (),
-            Err(err) => break Err(err).context("draw"),
+    // The package and all installed packages it transitively depends on, each once.
+    fn dependency_closure<'b>(&self, package: &'b PackageDesc) -> Vec<&'b PackageDesc>
+    where
+        'a: 'b,
+    {
+        let mut visited: BTreeSet<&str> = Default::default();
+        let mut closure = Vec::new();
+        let mut stack = vec![package];
+        while let Some(package) = stack.pop() {
+            if !visited.insert(package.name.as_str()) {
+                continue;
+            }
+            closure.push(package);
+            stack.extend(
+                self.resolved_dependencies(package)
+                    .into_iter()
+                    .map(|name| &self.packages[name]),
+            );
         }
-        let event = match crossterm::event::read() {
-            Ok(event) => event,
-            Err(err) => break Err(err).context("crossterm::event::read"),
-        };
-        if app.event(event) {
-            break Ok(());
+        closure
+    }
+
+    // how many of the optional dependencies are satisfied by installed packages
+    fn installed_optional_dependencies(&self, package: &PackageDesc) -> usize {
+        package
+            .optional_dependencies
+            .iter()
+            .filter(|dep| {
+                !resolve_dependency(self.packages, &self.providers, dep.name.as_str()).is_empty()
+            })
+            .count()
+    }
+
+    // The shortest chain of dependencies from an explicitly installed package down to the package,
+    // answering what pulled it in. `None` if no explicitly installed package requires it.
+    fn explicit_chain(&self, package: &'a PackageDesc) -> Option<Vec<&'a str>> {
+        // package -> the package it was reached from, towards `package`
+        let mut previous: BTreeMap<&str, &str> = Default::default();
+        let mut queue = std::collections::VecDeque::from([package.name.as_str()]);
+        while let Some(name) = queue.pop_front() {
+            if name != package.name && matches!(self.packages[name].reason, Reason::Explicit) {
+                let mut chain = vec![name];
+                while let Some(next) = previous.get(chain.last().unwrap()) {
+                    chain.push(next);
+                }
+                return Some(chain);
+            }
+            for dependant in self.dependants.get(name).into_iter().flatten() {
+                if *dependant != package.name && !previous.contains_key(dependant) {
+                    previous.insert(dependant, name);
+                    queue.push_back(dependant);
+                }
+            }
         }
-    };
+        None
+    }
 
-    crossterm::execute!(terminal.backend_mut(), LeaveAlternateScreen)
-        .context("LeaveAlternateScreen")?;
-    crossterm::terminal::disable_raw_mode().context("disable_raw_mode")?;
-    terminal.show_cursor().context("show_cursor")?;
+    // Sum of the sizes of the package and all installed packages it transitively depends on.
+    fn total_size(&self, package: &PackageDesc) -> u64 {
+        if let Some(size) = self.total_size_cache.borrow().get(&package.name) {
+            return *size;
+        }
+        let total = self
+            .dependency_closure(package)
+            .iter()
+            .map(|package| package.size.unwrap_or(0))
+            .sum();
+        self.total_size_cache
+            .borrow_mut()
+            .insert(package.name.clone(), total);
+        total
+    }
+
+    // The matcher if it is up to date with a non empty search term.
+    fn active_matcher(&self) -> Option<&Matcher> {
+        self.matcher.as_ref().filter(|matcher| {
+            !self.search.is_empty()
+                && matcher.is_for(self.search.as_str(), self.search_mode, self.case_sensitive)
+        })
+    }
 
+    // whether the text matches the active search term
+    fn search_matches(&self, text: &str) -> bool {
+        self.active_matcher()
+            .is_some_and(|matcher| matcher.score(text).is_some())
+    }
+
+    // Recreates the matcher only if the search settings changed so that regexes aren't recompiled on
+    // every search step.
+    fn update_matcher(&mut self) -> Result<(), regex::Error> {
+        let up_to_date = self.matcher.as_ref().is_some_and(|matcher| {
+            matcher.is_for(self.search.as_str(), self.search_mode, self.case_sensitive)
+        });
+        if !up_to_date {
+            self.matcher = Some(Matcher::new(
+                self.search.as_str(),
+                self.search_mode,
+                self.case_sensitive,
+            )?);
+            self.clear_search_matches();
+        }
+        Ok(())
+    }
+
+    // The next search step finds the matches again.
+    fn clear_search_matches(&mut self) {
+        for c in &mut self.columns {
+            c.search_matches = None;
+        }
+    }
+
+    // Returns whether selection changed. Moves to the next match in the active column in list order
+    // or, for fuzzy search, in score order. Fuzzy search starts at the best match.
+    fn search(&mut self, search_direction: SearchDirection) -> bool {
+        if self.search.is_empty() {
+            return false;
+        }
+        if self.update_matcher().is_err() {
+            return false;
+        }
+        let matcher = self.matcher.as_ref().unwrap();
+        let scope = self.search_scope;
+        let c = self.columns.get_mut(self.active_column).unwrap();
+        let index = match c.list_state.selected() {
+            Some(i) => i,
+            None => return false,
+        };
+        let matches = c.search_matches.get_or_insert_with(|| {
+            let mut matches: Vec<(usize, i64)> = c
+                .packages
+                .iter()
+                .enumerate()
+                .filter_map(|(i, package)| Some((i, matcher.score_package(package, scope)?)))
+                .collect();
+            // stable so that equal scores stay in list order
+            matches.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+            matches
+        });
+        if matches.is_empty() {
+            self.message = Some(format!("no match for {:?}", self.search));
+            return false;
+        }
+        let len = matches.len();
+        let next = match matches.iter().position(|(i, _)| *i == index) {
+            Some(rank) => match search_direction {
+                SearchDirection::Down => (rank + 1) % len,
+                SearchDirection::Up => (rank + len - 1) % len,
+            },
+            None => match (self.search_mode, search_direction) {
+                (SearchMode::Fuzzy, _) => 0,
+                (_, SearchDirection::Down) => {
+                    matches.iter().position(|(i, _)| *i > index).unwrap_or(0)
+                }
+                (_, SearchDirection::Up) => matches
+                    .iter()
+                    .rposition(|(i, _)| *i < index)
+                    .unwrap_or(len - 1),
+            },
+        };
+        self.message = Some(format!("match {}/{len} for {:?}", next + 1, self.search));
+        let next = matches[next].0;
+        c.list_state.select(Some(next));
+        next != index
+    }
+}
+
+// Styles the byte ranges of the text as highlighted.
+fn highlight<'a>(text: &'a str, ranges: &[std::ops::Range<usize>], style: Style) -> Spans<'a> {
+    let mut spans = Vec::new();
+    let mut position = 0;
+    for range in ranges {
+        if position < range.start {
+            spans.push(Span::raw(&text[position..range.start]));
+        }
+        spans.push(Span::styled(&text[range.clone()], style));
+        position = range.end;
+    }
+    if position < text.len() {
+        spans.push(Span::raw(&text[position..]));
+    }
+    Spans(spans)
+}
+
+#[test]
+fn highlight_splits_text() {
+    let spans = highlight("firefox", &[0..1, 4..5], Style::default());
+    let contents: Vec<&str> = spans.0.iter().map(|span| span.content.as_ref()).collect();
+    assert_eq!(contents, ["f", "ire", "f", "ox"]);
+}
+
+// Removes the colors of everything drawn before it and keeps the other styling.
+struct StripColors;
+
+impl Widget for StripColors {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for x in area.left()..area.right() {
+            for y in area.top()..area.bottom() {
+                let cell = buf.get_mut(x, y);
+                cell.fg = Color::Reset;
+                cell.bg = Color::Reset;
+            }
+        }
+    }
+}
+
+// Guesses from `COLORFGBG`, which some terminals set to `foreground;background` color numbers.
+fn detect_theme() -> args::Theme {
+    let background = std::env::var("COLORFGBG")
+        .ok()
+        .and_then(|colors| colors.rsplit(';').next()?.parse::<u8>().ok());
+    match background {
+        Some(7 | 9..=15) => args::Theme::Light,
+        _ => args::Theme::Dark,
+    }
+}
+
+#[test]
+fn no_color_keeps_modifiers() {
+    let packages = test_packages([test_package("firefox", Reason::Explicit, &[], &[])]);
+    let mut app = App::new(&packages);
+    app.show_help = false;
+    app.search = "fire".to_string();
+    app.update_matcher().unwrap();
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+    let matched = |terminal: &Terminal<tui::backend::TestBackend>| {
+        let buffer = terminal.backend().buffer();
+        let area = buffer.area;
+        (0..area.height)
+            .flat_map(|y| (0..area.width).map(move |x| (x, y)))
+            .map(|(x, y)| buffer.get(x, y).clone())
+            .find(|cell| cell.modifier.contains(Modifier::REVERSED))
+            .unwrap()
+    };
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    assert_eq!(matched(&terminal).fg, Color::Yellow);
+    app.colors = false;
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    assert_eq!(matched(&terminal).fg, Color::Reset);
+}
+
+#[test]
+fn dependencies_grayed_in_lists() {
+    let packages = test_packages([
+        test_package("app", Reason::Explicit, &["lib"], &[]),
+        test_package("lib", Reason::Dependency, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.show_help = false;
+    app.set_filter(Filter::Explicit, false);
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+    // of the first letter of the name in the center list, after the border
+    let color = |terminal: &Terminal<tui::backend::TestBackend>, name: &str| {
+        let buffer = terminal.backend().buffer();
+        let row = |y| -> String { (31..59).map(|x| buffer.get(x, y).symbol.as_str()).collect() };
+        let y = (1..buffer.area.height)
+            .find(|y| row(*y).starts_with(name))
+            .unwrap();
+        buffer.get(31, y).fg
+    };
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    assert_eq!(color(&terminal, "app"), Color::Reset);
+    assert_eq!(color(&terminal, "lib"), Color::DarkGray);
+    app.colors = false;
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    assert_eq!(color(&terminal, "lib"), Color::Reset);
+}
+
+// `HELP` with the keys of the keymap
+fn help_rows(keymap: &Keymap) -> Vec<(String, &'static str)> {
+    HELP.iter()
+        .map(|(actions, description)| {
+            let keys = match actions.is_empty() {
+                true => "other keys".to_string(),
+                false => keymap.describe(actions),
+            };
+            (keys, *description)
+        })
+        .collect()
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    (area.left()..area.right()).contains(&column) && (area.top()..area.bottom()).contains(&row)
+}
+
+#[cfg(test)]
+fn test_package(
+    name: &str,
+    reason: Reason,
+    dependencies: &[&str],
+    provides: &[&str],
+) -> PackageDesc {
+    PackageDesc {
+        name: name.to_string(),
+        reason,
+        dependencies: dependencies
+            .iter()
+            .map(|dep| leptohadron::Dependency::parse(dep))
+            .collect(),
+        provides: provides.iter().map(ToString::to_string).collect(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+fn test_packages(packages: impl IntoIterator<Item = PackageDesc>) -> BTreeMap<String, PackageDesc> {
+    packages
+        .into_iter()
+        .map(|package| (package.name.clone(), package))
+        .collect()
+}
+
+#[test]
+fn orphans_consider_provides() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["sh"], &[]),
+        test_package("bash", Reason::Dependency, &[], &["sh"]),
+        test_package("c", Reason::Dependency, &[], &[]),
+        test_package("d", Reason::Explicit, &[], &[]),
+    ]);
+    let app = App::new(&packages);
+    let orphans: Vec<&str> = packages
+        .values()
+        .filter(|package| is_orphan(package, &app.dependants))
+        .map(|package| package.name.as_str())
+        .collect();
+    assert_eq!(orphans, ["c"]);
+}
+
+#[test]
+fn orphan_shown_first_in_details() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b"], &[]),
+        test_package("b", Reason::Dependency, &[], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.show_help = false;
+    app.set_filter(Filter::Explicit, false);
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+    let first_detail_row = |app: &mut App, terminal: &mut Terminal<tui::backend::TestBackend>| {
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        // the row after the separator below the list
+        let area = app.columns[1].list_area;
+        let buffer = terminal.backend().buffer();
+        (area.left()..area.right())
+            .map(|x| buffer.get(x, area.bottom() + 1).symbol.as_str())
+            .collect::<String>()
+    };
+    assert!(first_detail_row(&mut app, &mut terminal).starts_with("name:"));
+    app.event(key(KeyCode::Char('0')));
+    assert_eq!(app.columns[1].selected().unwrap().name, "c");
+    assert!(first_detail_row(&mut app, &mut terminal).starts_with("⚠ orphan (not required"));
+}
+
+#[test]
+fn removal_closure() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b", "c", "e"], &[]),
+        // only required by a
+        test_package("b", Reason::Dependency, &["d"], &[]),
+        // also required by f
+        test_package("c", Reason::Dependency, &[], &[]),
+        // only required by b
+        test_package("d", Reason::Dependency, &[], &[]),
+        // explicitly installed
+        test_package("e", Reason::Explicit, &[], &[]),
+        test_package("f", Reason::Explicit, &["c"], &[]),
+    ]);
+    let app = App::new(&packages);
+    let closure = app.removal_closure(&packages["a"]);
+    assert_eq!(closure.into_iter().collect::<Vec<_>>(), ["b", "d"]);
+}
+
+#[cfg(test)]
+fn optional(name: &str) -> leptohadron::OptionalDependency {
+    leptohadron::OptionalDependency {
+        name: name.to_string(),
+        description: None,
+    }
+}
+
+#[test]
+fn removal_closure_skips_optional_dependencies() {
+    let mut a = test_package("a", Reason::Explicit, &[], &[]);
+    a.optional_dependencies = vec![optional("b")];
+    let packages = test_packages([a, test_package("b", Reason::Dependency, &[], &[])]);
+    let app = App::new(&packages);
+    assert!(app.removal_closure(&packages["a"]).is_empty());
+}
+
+#[test]
+fn removal_closure_ignores_optional_dependants() {
+    let mut c = test_package("c", Reason::Explicit, &[], &[]);
+    c.optional_dependencies = vec![optional("b")];
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b"], &[]),
+        test_package("b", Reason::Dependency, &[], &[]),
+        c,
+    ]);
+    let app = App::new(&packages);
+    let closure = app.removal_closure(&packages["a"]);
+    assert_eq!(closure.into_iter().collect::<Vec<_>>(), ["b"]);
+}
+
+#[test]
+fn total_size_with_cycle() {
+    let mut packages = [
+        test_package("a", Reason::Explicit, &["b"], &[]),
+        test_package("b", Reason::Dependency, &["c"], &[]),
+        test_package("c", Reason::Dependency, &["b"], &[]),
+        test_package("d", Reason::Explicit, &[], &[]),
+    ];
+    for (package, size) in packages.iter_mut().zip([1, 2, 4, 8]) {
+        package.size = Some(size);
+    }
+    let packages = test_packages(packages);
+    let app = App::new(&packages);
+    assert_eq!(app.total_size(&packages["a"]), 7);
+    assert_eq!(app.total_size(&packages["b"]), 6);
+    // cached
+    assert_eq!(app.total_size(&packages["a"]), 7);
+}
+
+#[cfg(test)]
+fn key(code: KeyCode) -> Event {
+    Event::Key(crossterm::event::KeyEvent::new(
+        code,
+        crossterm::event::KeyModifiers::NONE,
+    ))
+}
+
+#[test]
+fn vim_keys() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b"], &[]),
+        test_package("b", Reason::Explicit, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.event(key(KeyCode::Char('j')));
+    assert_eq!(app.columns[1].selected().unwrap().name, "b");
+    app.event(key(KeyCode::Char('k')));
+    assert_eq!(app.columns[1].selected().unwrap().name, "a");
+    app.event(key(KeyCode::Char('l')));
+    assert_eq!(app.active_column, 2);
+    app.event(key(KeyCode::Char('h')));
+    assert_eq!(app.active_column, 1);
+    // typed into the search term while searching
+    app.event(key(KeyCode::Char('/')));
+    app.event(key(KeyCode::Char('j')));
+    assert_eq!(app.search, "j");
+    assert_eq!(app.columns[1].selected().unwrap().name, "a");
+}
+
+#[test]
+fn quit_keys() {
+    let packages = test_packages([test_package("a", Reason::Explicit, &[], &[])]);
+    let mut app = App::new(&packages);
+    assert!(!app.event(key(KeyCode::Char('c'))));
+    assert!(app.event(key(KeyCode::Char('q'))));
+    assert_eq!(app.picked(), Some("a"));
+    assert!(app.event(Event::Key(crossterm::event::KeyEvent::new(
+        KeyCode::Char('c'),
+        KeyModifiers::CONTROL,
+    ))));
+    assert_eq!(app.picked(), None);
+}
+
+#[test]
+fn terminal_too_small() {
+    let packages = test_packages([test_package("a", Reason::Explicit, &[], &[])]);
+    let mut app = App::new(&packages);
+    let text = |app: &mut App, width, height| {
+        let mut terminal = Terminal::new(tui::backend::TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content
+            .iter()
+            .map(|cell| cell.symbol.as_str())
+            .collect::<String>()
+    };
+    assert!(text(&mut app, 30, 20).contains("terminal too small"));
+    assert!(text(&mut app, 90, 5).contains("terminal too small"));
+    assert!(!text(&mut app, 90, 20).contains("terminal too small"));
+}
+
+#[test]
+fn help_hidden_when_too_short() {
+    let packages = test_packages([test_package("a", Reason::Explicit, &[], &[])]);
+    let mut app = App::new(&packages);
+    let shows_help = |app: &mut App, height| {
+        let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, height)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let top: String = (0..buffer.area.width)
+            .map(|x| buffer.get(x, 0).symbol.as_str())
+            .collect();
+        top.contains("Help")
+    };
+    assert!(shows_help(&mut app, 60));
+    assert!(!shows_help(&mut app, 30));
+    // the choice is kept for when the terminal grows again
+    assert!(app.show_help);
+    app.event(key(KeyCode::Char('?')));
+    app.event(key(KeyCode::Char('?')));
+    assert_eq!(
+        app.message.as_deref(),
+        Some("the terminal is too short for the help")
+    );
+}
+
+#[test]
+fn confirm_quit() {
+    let packages = test_packages([test_package("a", Reason::Explicit, &[], &[])]);
+    let mut app = App::new(&packages);
+    app.confirm_quit = true;
+    assert!(!app.event(key(KeyCode::Char('q'))));
+    assert_eq!(app.message.as_deref(), Some("Quit? (y/n)"));
+    // any other key cancels without doing anything else
+    assert!(!app.event(key(KeyCode::Char('?'))));
+    assert!(app.show_help);
+    app.event(key(KeyCode::Char('q')));
+    assert!(app.event(key(KeyCode::Char('y'))));
+}
+
+#[test]
+fn dependency_tree() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b", "c"], &[]),
+        test_package("b", Reason::Dependency, &["d"], &[]),
+        test_package("c", Reason::Dependency, &["d"], &[]),
+        test_package("d", Reason::Dependency, &["b"], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.event(key(KeyCode::Char('t')));
+    let rows = |app: &App| -> Vec<String> {
+        let tree = app.tree.as_ref().unwrap();
+        tree.rows.iter().map(|row| row.path.join("/")).collect()
+    };
+    assert_eq!(rows(&app), ["a", "a/b", "a/c"]);
+    app.event(key(KeyCode::Down));
+    app.event(key(KeyCode::Enter));
+    assert_eq!(rows(&app), ["a", "a/b", "a/b/d", "a/c"]);
+    // the cycle b -> d -> b ends at the second b
+    app.event(key(KeyCode::Down));
+    app.event(key(KeyCode::Char(' ')));
+    app.event(key(KeyCode::Down));
+    app.event(key(KeyCode::Char(' ')));
+    assert_eq!(rows(&app), ["a", "a/b", "a/b/d", "a/b/d/b", "a/c"]);
+    assert!(app.tree.as_ref().unwrap().rows[3].cycle);
+    // collapsing hides everything below
+    app.event(key(KeyCode::Char('1')));
+    app.event(key(KeyCode::Enter));
+    assert_eq!(rows(&app), ["a"]);
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    app.event(key(KeyCode::Char('t')));
+    assert!(app.tree.is_none());
+}
+
+#[test]
+fn page_size() {
+    let packages = test_packages(
+        (0..50).map(|i| test_package(&format!("{i:02}"), Reason::Explicit, &[], &[])),
+    );
+    let mut app = App::new(&packages);
+    app.show_help = false;
+    // not drawn yet
+    app.event(key(KeyCode::PageDown));
+    assert_eq!(app.columns[1].list_state.selected(), Some(10));
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    let height = app.columns[1].list_area.height as usize;
+    app.event(key(KeyCode::PageDown));
+    assert_eq!(app.columns[1].list_state.selected(), Some(10 + height));
+    app.page_size = Some(3);
+    app.event(key(KeyCode::PageUp));
+    assert_eq!(app.columns[1].list_state.selected(), Some(7 + height));
+}
+
+#[test]
+fn home_end_keys() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &[], &[]),
+        test_package("b", Reason::Explicit, &["a"], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.event(key(KeyCode::End));
+    assert_eq!(app.columns[1].selected().unwrap().name, "b");
+    // the side lists follow
+    assert_eq!(app.columns[2].selected().unwrap().name, "a");
+    app.event(key(KeyCode::Home));
+    assert_eq!(app.columns[1].selected().unwrap().name, "a");
+    assert!(app.columns[2].packages.is_empty());
+}
+
+#[test]
+fn details_layouts_give_lists_more_space() {
+    let packages = test_packages([test_package("a", Reason::Explicit, &[], &[])]);
+    let mut app = App::new(&packages);
+    app.show_help = false;
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+    let mut heights = Vec::new();
+    for _ in 0..3 {
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        heights.push(app.columns[1].list_area.height);
+        app.event(key(KeyCode::Char('D')));
+    }
+    assert_eq!(app.details_layout, DetailsLayout::Columns);
+    assert!(heights[0] < heights[1] && heights[1] < heights[2]);
+}
+
+#[test]
+fn subtree_zoom() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b"], &[]),
+        test_package("b", Reason::Dependency, &["c"], &[]),
+        test_package("c", Reason::Dependency, &["b"], &[]),
+        test_package("d", Reason::Explicit, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    let names = |app: &App| -> Vec<String> {
+        app.columns[1]
+            .packages
+            .iter()
+            .map(|package| package.name.clone())
+            .collect()
+    };
+    assert_eq!(names(&app), ["a", "d"]);
+    app.event(key(KeyCode::Char('Z')));
+    assert_eq!(names(&app), ["a", "b", "c"]);
+    assert!(app.columns[1].title.starts_with("Subtree of a"));
+    assert_eq!(app.preferences().filters, [Filter::Explicit]);
+    app.event(key(KeyCode::Esc));
+    assert_eq!(names(&app), ["a", "d"]);
+    assert_eq!(app.columns[1].selected().unwrap().name, "a");
+
+    // focusing a package outside of the subtree leaves it
+    app.event(key(KeyCode::Char('Z')));
+    app.event(key(KeyCode::Char('h')));
+    app.columns[0].packages = vec![&packages["d"]];
+    app.columns[0].list_state.select(Some(0));
+    app.event(key(KeyCode::Enter));
+    assert!(app.subtree.is_none());
+    assert_eq!(app.filters, [Filter::Explicit]);
+    assert_eq!(app.columns[1].selected().unwrap().name, "d");
+}
+
+#[test]
+fn focus_keeps_passed_filters() {
+    let mut packages = [
+        test_package("a", Reason::Explicit, &["b"], &[]),
+        test_package("b", Reason::Dependency, &[], &[]),
+    ];
+    packages[0].size = Some(1000);
+    packages[1].size = Some(1000);
+    let packages = test_packages(packages);
+    let mut app = App::new(&packages);
+    app.set_filter(Filter::MinSize(100), true);
+    app.event(key(KeyCode::Char('l')));
+    app.event(key(KeyCode::Enter));
+    assert_eq!(app.columns[1].selected().unwrap().name, "b");
+    // only the explicit filter excluded it
+    assert_eq!(app.filters, [Filter::MinSize(100)]);
+}
+
+#[test]
+fn configured_keys() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &[], &[]),
+        test_package("b", Reason::Explicit, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.keymap = Config::parse("[keys]\nmove-down = \"d\"").unwrap().keymap;
+    // no longer bound so it jumps to a package instead
+    app.event(key(KeyCode::Char('j')));
+    assert_eq!(app.columns[1].selected().unwrap().name, "a");
+    app.event(key(KeyCode::Char('d')));
+    assert_eq!(app.columns[1].selected().unwrap().name, "b");
+    assert!(
+        help_rows(&app.keymap).contains(&("up, k, d, PgUp, PgDown".to_string(), "move in list"))
+    );
+}
+
+#[test]
+fn empty_database() {
+    let packages = BTreeMap::new();
+    let mut app = App::new(&packages);
+    app.show_help = false;
+    app.backend = Box::new(Pacman {
+        dbpath: "/nowhere".to_string(),
+    });
+    for char in "jklh10sneoRuOFwNimf[]".chars() {
+        app.event(key(KeyCode::Char(char)));
+    }
+    for code in [KeyCode::Enter, KeyCode::PageDown, KeyCode::Backspace] {
+        app.event(key(code));
+    }
+    for prompt in "/g>".chars() {
+        app.event(key(KeyCode::Char(prompt)));
+        app.event(key(KeyCode::Char('1')));
+        app.event(key(KeyCode::Enter));
+        app.event(key(KeyCode::Esc));
+    }
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    let text: String = terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|cell| cell.symbol.as_str())
+        .collect();
+    assert!(text.contains("No packages found at /nowhere"));
+}
+
+#[test]
+fn mouse_click_selects_entry() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b", "c"], &[]),
+        test_package("b", Reason::Dependency, &[], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.show_help = false;
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    let area = app.columns[2].list_area;
+    app.event(Event::Mouse(MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column: area.x,
+        row: area.y + 1,
+        modifiers: crossterm::event::KeyModifiers::NONE,
+    }));
+    assert_eq!(app.active_column, 2);
+    assert_eq!(app.columns[2].selected().unwrap().name, "c");
+}
+
+#[test]
+fn mouse_scroll_moves_list_under_cursor() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &[], &[]),
+        test_package("b", Reason::Explicit, &[], &[]),
+        test_package("c", Reason::Explicit, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.scroll_step = 3;
+    app.show_help = false;
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    let area = app.columns[1].list_area;
+    let scroll = |kind| {
+        Event::Mouse(MouseEvent {
+            kind,
+            column: area.x,
+            row: area.y,
+            modifiers: crossterm::event::KeyModifiers::NONE,
+        })
+    };
+    app.event(scroll(MouseEventKind::ScrollDown));
+    // clamped to the end of the list
+    assert_eq!(app.columns[1].selected().unwrap().name, "c");
+    app.event(scroll(MouseEventKind::ScrollUp));
+    assert_eq!(app.columns[1].selected().unwrap().name, "a");
+}
+
+#[test]
+fn scrollbar_only_when_list_overflows() {
+    let scrollbar_rows = |count: usize| {
+        let packages = test_packages(
+            (0..count).map(|i| test_package(&i.to_string(), Reason::Explicit, &[], &[])),
+        );
+        let mut app = App::new(&packages);
+        app.show_help = false;
+        let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let area = app.columns[1].list_area;
+        let buffer = terminal.backend().buffer();
+        (area.top()..area.bottom())
+            .filter(|row| buffer.get(area.right(), *row).symbol == "█")
+            .count()
+    };
+    assert_eq!(scrollbar_rows(5), 0);
+    assert!(scrollbar_rows(1000) > 0);
+}
+
+#[test]
+fn search_ignores_case_by_default() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &[], &[]),
+        test_package("libreoffice-Fresh", Reason::Explicit, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.search = "fresh".to_string();
+    app.case_sensitive = true;
+    assert!(!app.search(SearchDirection::Down));
+    app.case_sensitive = false;
+    assert!(app.search(SearchDirection::Down));
+    assert_eq!(app.columns[1].selected().unwrap().name, "libreoffice-Fresh");
+}
+
+#[test]
+fn fuzzy_search_steps_in_score_order() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &[], &[]),
+        test_package("f-i-r-e", Reason::Explicit, &[], &[]),
+        test_package("firefox", Reason::Explicit, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.search = "fire".to_string();
+    app.search_mode = SearchMode::Fuzzy;
+    let mut selected = || {
+        app.search(SearchDirection::Down);
+        app.columns[1].selected().unwrap().name.as_str()
+    };
+    assert_eq!(selected(), "firefox");
+    assert_eq!(selected(), "f-i-r-e");
+    assert_eq!(selected(), "firefox");
+}
+
+#[test]
+fn substring_search_wraps_around() {
+    let packages = test_packages([
+        test_package("a1", Reason::Explicit, &[], &[]),
+        test_package("b", Reason::Explicit, &[], &[]),
+        test_package("c1", Reason::Explicit, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.columns[1].list_state.select(Some(1));
+    app.search = "1".to_string();
+    assert!(app.search(SearchDirection::Up));
+    assert_eq!(app.columns[1].selected().unwrap().name, "a1");
+    assert!(app.search(SearchDirection::Up));
+    assert_eq!(app.columns[1].selected().unwrap().name, "c1");
+    assert!(app.search(SearchDirection::Down));
+    assert_eq!(app.columns[1].selected().unwrap().name, "a1");
+}
+
+#[test]
+fn count_installed_optional_dependencies() {
+    let mut main = test_package("main", Reason::Explicit, &[], &[]);
+    main.optional_dependencies = ["b", "virtual", "missing"]
+        .into_iter()
+        .map(|name| leptohadron::OptionalDependency {
+            name: name.to_string(),
+            description: None,
+        })
+        .collect();
+    let packages = test_packages([
+        main,
+        test_package("b", Reason::Dependency, &[], &[]),
+        test_package("c", Reason::Dependency, &[], &["virtual=1"]),
+    ]);
+    let app = App::new(&packages);
+    assert_eq!(app.installed_optional_dependencies(&packages["main"]), 2);
+    assert_eq!(app.installed_optional_dependencies(&packages["b"]), 0);
+}
+
+#[test]
+fn explicit_chain_is_shortest() {
+    let packages = test_packages([
+        test_package("shell", Reason::Explicit, &["mutter", "input"], &[]),
+        test_package("mutter", Reason::Dependency, &["input"], &[]),
+        test_package("input", Reason::Dependency, &[], &[]),
+        test_package("a", Reason::Dependency, &["b"], &[]),
+        test_package("b", Reason::Dependency, &["a"], &[]),
+    ]);
+    let app = App::new(&packages);
+    let chain = |name: &str| app.explicit_chain(&packages[name]);
+    assert_eq!(chain("mutter"), Some(vec!["shell", "mutter"]));
+    assert_eq!(chain("input"), Some(vec!["shell", "input"]));
+    // cycles without an explicit package
+    assert_eq!(chain("a"), None);
+    assert_eq!(chain("shell"), None);
+}
+
+#[test]
+fn filter_while_typing() {
+    let packages = test_packages([
+        test_package("axbxc", Reason::Explicit, &[], &[]),
+        test_package("d", Reason::Explicit, &[], &[]),
+        test_package("zbc", Reason::Explicit, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    let names = |app: &App| -> Vec<String> {
+        let packages = &app.columns[1].packages;
+        packages
+            .iter()
+            .map(|package| package.name.clone())
+            .collect()
+    };
+    app.search_mode = SearchMode::Fuzzy;
+    app.event(key(KeyCode::Char('|')));
+    app.event(key(KeyCode::Char('b')));
+    assert_eq!(names(&app), ["axbxc", "zbc"]);
+    app.event(key(KeyCode::Char('c')));
+    // the consecutive match ranks first
+    assert_eq!(names(&app), ["zbc", "axbxc"]);
+    app.event(key(KeyCode::Backspace));
+    app.event(key(KeyCode::Backspace));
+    assert_eq!(names(&app).len(), 3);
+    app.event(key(KeyCode::Char('x')));
+    app.event(key(KeyCode::Enter));
+    assert!(app.prompt.is_none());
+    assert_eq!(names(&app), ["axbxc"]);
+    assert_eq!(app.columns[1].title, "All [explicit, matching \"x\"] — 0 B");
+    // case sensitive
+    app.event(key(KeyCode::Char('i')));
+    assert_eq!(names(&app), ["axbxc"]);
+    app.event(key(KeyCode::Char('|')));
+    app.event(key(KeyCode::Char('X')));
+    assert!(names(&app).is_empty());
+    app.event(key(KeyCode::Esc));
+    assert_eq!(names(&app).len(), 3);
+    assert_eq!(app.filters, [Filter::Explicit]);
+}
+
+#[test]
+fn invalid_regex_keeps_prompt_and_selection() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &[], &[]),
+        test_package("b", Reason::Explicit, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.search_mode = SearchMode::Regex;
+    for code in [KeyCode::Char('/'), KeyCode::Char('('), KeyCode::Enter] {
+        app.event(key(code));
+    }
+    assert!(matches!(app.prompt, Some(Prompt::Search)));
+    assert!(app.prompt_error.is_some());
+    assert_eq!(app.columns[1].selected().unwrap().name, "a");
+    for code in [KeyCode::Backspace, KeyCode::Char('b'), KeyCode::Enter] {
+        app.event(key(code));
+    }
+    assert!(app.prompt.is_none());
+    assert_eq!(app.columns[1].selected().unwrap().name, "b");
+}
+
+#[test]
+fn filters_stack() {
+    let mut packages = [
+        test_package("a", Reason::Explicit, &[], &[]),
+        test_package("b", Reason::Explicit, &[], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+    ];
+    packages[0].size = Some(10);
+    packages[1].size = Some(1000);
+    packages[2].size = Some(1000);
+    let packages = test_packages(packages);
+    let mut app = App::new(&packages);
+    let names = |app: &App| {
+        app.columns[1]
+            .packages
+            .iter()
+            .map(|package| package.name.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&app), ["a", "b"]);
+    app.set_filter(Filter::MinSize(100), true);
+    assert_eq!(names(&app), ["b"]);
+    app.set_filter(Filter::Explicit, false);
+    assert_eq!(names(&app), ["b", "c"]);
+    app.set_filter(Filter::Orphans, true);
+    assert_eq!(names(&app), ["c"]);
+    // orphans and explicit exclude each other
+    app.set_filter(Filter::Explicit, true);
+    assert_eq!(names(&app), ["b"]);
+    assert_eq!(app.columns[1].title, "All [>= 100 B, explicit] — 1000 B");
+    app.set_filter(Filter::MinSize(100), false);
+    assert_eq!(app.columns[1].title, "All [explicit] — 1010 B");
+}
+
+#[test]
+fn leaves_filter() {
+    let packages = test_packages([
+        test_package("app", Reason::Explicit, &["lib"], &[]),
+        test_package("lib", Reason::Explicit, &[], &[]),
+        test_package("orphan", Reason::Dependency, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.set_filter(Filter::Explicit, false);
+    app.event(key(KeyCode::Char('L')));
+    let names: Vec<&str> = app.columns[1]
+        .packages
+        .iter()
+        .map(|package| package.name.as_str())
+        .collect();
+    assert_eq!(names, ["app", "orphan"]);
+    assert_eq!(app.columns[1].title, "All [leaves] — 0 B");
+    app.event(key(KeyCode::Char('L')));
+    assert_eq!(app.columns[1].packages.len(), 3);
+}
+
+#[test]
+fn separate_optional_dependencies() {
+    let mut main = test_package("main", Reason::Explicit, &["c", "d"], &[]);
+    main.optional_dependencies = ["a", "b", "c"]
+        .into_iter()
+        .map(|name| leptohadron::OptionalDependency {
+            name: name.to_string(),
+            description: None,
+        })
+        .collect();
+    let mut packages = [
+        main,
+        test_package("a", Reason::Dependency, &[], &[]),
+        test_package("b", Reason::Dependency, &[], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+        test_package("d", Reason::Dependency, &[], &[]),
+    ];
+    for (package, size) in packages[1..].iter_mut().zip([8, 4, 1, 2]) {
+        package.size = Some(size);
+    }
+    let packages = test_packages(packages);
+    // the only explicit package is selected
+    let mut app = App::new(&packages);
+    let names = |app: &App| {
+        app.columns[2]
+            .packages
+            .iter()
+            .map(|package| package.name.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&app), ["a", "b", "c", "d"]);
+    app.event(key(KeyCode::Char('O')));
+    assert_eq!(names(&app), ["c", "d", "a", "b"]);
+    assert_eq!(app.columns[2].dimmed, BTreeSet::from(["a", "b"]));
+    // sorting again keeps them last
+    app.event(key(KeyCode::Char('s')));
+    assert_eq!(names(&app), ["d", "c", "a", "b"]);
+}
+
+#[test]
+fn export_main_view() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b"], &[]),
+        test_package("b", Reason::Dependency, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    let path = std::env::temp_dir().join(format!("leptohadron-export-{}", std::process::id()));
+    app.event(key(KeyCode::Char('x')));
+    for char in path.to_str().unwrap().chars() {
+        app.event(key(KeyCode::Char(char)));
+    }
+    app.event(key(KeyCode::Enter));
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(json[0]["name"], "a");
+    assert_eq!(json[0]["reason"], "explicit");
+    assert_eq!(json.as_array().unwrap().len(), 1);
+    assert!(app.message.is_some());
+}
+
+#[test]
+fn export_main_view_csv() {
+    let packages = test_packages([PackageDesc {
+        version: "1.0-1".to_string(),
+        size: Some(2048),
+        ..test_package("a", Reason::Explicit, &[], &[])
+    }]);
+    let mut app = App::new(&packages);
+    let path = std::env::temp_dir().join(format!("leptohadron-export-{}.csv", std::process::id()));
+    app.event(key(KeyCode::Char('X')));
+    for char in path.to_str().unwrap().chars() {
+        app.event(key(KeyCode::Char(char)));
+    }
+    app.event(key(KeyCode::Enter));
+    let csv = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        csv,
+        "name,version,size,reason,install_date\na,1.0-1,2048,explicit,\n"
+    );
+}
+
+// One row per package with a header. Unknown sizes and dates are empty.
+fn to_csv(packages: &[&PackageDesc]) -> String {
+    let mut csv = "name,version,size,reason,install_date\n".to_string();
+    for package in packages {
+        let fields = [
+            csv_field(&package.name),
+            csv_field(&package.version),
+            package
+                .size
+                .map(|size| size.to_string())
+                .unwrap_or_default(),
+            match package.reason {
+                Reason::Explicit => "explicit",
+                Reason::Dependency => "dependency",
+            }
+            .to_string(),
+            package.install_date.map(format_date).unwrap_or_default(),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+// Quotes the field if it contains a separator, quote or line break.
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[test]
+fn csv_quoting() {
+    assert_eq!(csv_field("a"), "a");
+    assert_eq!(csv_field("a,b"), "\"a,b\"");
+    assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+}
+
+fn run_query(
+    app: &App,
+    query: args::Query,
+    root: Option<&str>,
+    out: &mut impl std::io::Write,
+) -> Result<()> {
+    match query {
+        args::Query::List => {
+            for package in app.packages.values() {
+                writeln!(out, "{} {}", package.name, package.version)?;
+            }
+        }
+        args::Query::Orphans => {
+            for package in app.packages.values() {
+                if Filter::Orphans.filter(package, &app.dependants) {
+                    writeln!(out, "{}", package.name)?;
+                }
+            }
+        }
+        args::Query::Json => {
+            let packages: Vec<&PackageDesc> = app.packages.values().collect();
+            serde_json::to_writer_pretty(&mut *out, &packages).context("serialize")?;
+            writeln!(out)?;
+        }
+        args::Query::Graph(format) => {
+            let graph = DependencyGraph::new(app, root)?;
+            match format {
+                args::GraphFormat::Dot => graph.write_dot(out)?,
+                args::GraphFormat::GraphMl => graph.write_graphml(out)?,
+                args::GraphFormat::Mermaid => graph.write_mermaid(out)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+// One node per package and an edge to every installed dependency, written in several formats.
+struct DependencyGraph<'a> {
+    nodes: BTreeSet<&'a str>,
+    // from, to and whether the dependency is only optional
+    edges: Vec<(&'a str, &'a str, bool)>,
+}
+
+impl<'a> DependencyGraph<'a> {
+    // `root` restricts the graph to the package and everything it transitively depends on.
+    fn new(app: &App<'a>, root: Option<&str>) -> Result<Self> {
+        let nodes: BTreeSet<&str> = match root {
+            Some(root) => {
+                let package = app
+                    .packages
+                    .get(root)
+                    .with_context(|| format!("package {root:?} is not installed"))?;
+                let mut names = BTreeSet::from([package.name.as_str()]);
+                let mut todo = vec![package];
+                while let Some(package) = todo.pop() {
+                    for dep in app.resolved_dependencies(package) {
+                        if names.insert(dep) {
+                            todo.push(&app.packages[dep]);
+                        }
+                    }
+                }
+                names
+            }
+            None => app.packages.keys().map(String::as_str).collect(),
+        };
+        let mut edges = Vec::new();
+        for name in &nodes {
+            let package = &app.packages[*name];
+            let required =
+                app.resolve_all(package.dependencies.iter().map(|dep| dep.name.as_str()));
+            for dep in app.resolved_dependencies(package) {
+                edges.push((*name, dep, !required.contains(dep)));
+            }
+        }
+        Ok(Self { nodes, edges })
+    }
+
+    // Optional dependencies are dashed.
+    fn write_dot(&self, out: &mut impl std::io::Write) -> Result<()> {
+        writeln!(out, "digraph dependencies {{")?;
+        for name in &self.nodes {
+            writeln!(out, "    {};", dot_quote(name))?;
+        }
+        for (from, to, optional) in &self.edges {
+            let (from, to) = (dot_quote(from), dot_quote(to));
+            match optional {
+                false => writeln!(out, "    {from} -> {to};")?,
+                true => writeln!(out, "    {from} -> {to} [style=dashed];")?,
+            }
+        }
+        writeln!(out, "}}")?;
+        Ok(())
+    }
+
+    // Edges have an `optional` attribute.
+    fn write_graphml(&self, out: &mut impl std::io::Write) -> Result<()> {
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        writeln!(
+            out,
+            r#"  <key id="optional" for="edge" attr.name="optional" attr.type="boolean"/>"#
+        )?;
+        writeln!(out, r#"  <graph id="dependencies" edgedefault="directed">"#)?;
+        for name in &self.nodes {
+            writeln!(out, r#"    <node id="{}"/>"#, xml_escape(name))?;
+        }
+        for (from, to, optional) in &self.edges {
+            writeln!(
+                out,
+                r#"    <edge source="{}" target="{}"><data key="optional">{optional}</data></edge>"#,
+                xml_escape(from),
+                xml_escape(to)
+            )?;
+        }
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</graphml>")?;
+        Ok(())
+    }
+
+    // Package names can contain characters that aren't allowed in Mermaid ids so nodes are numbered
+    // and labeled. Optional dependencies are dotted.
+    fn write_mermaid(&self, out: &mut impl std::io::Write) -> Result<()> {
+        let ids: BTreeMap<&str, usize> = self.nodes.iter().copied().zip(0..).collect();
+        writeln!(out, "graph TD")?;
+        for (name, id) in &ids {
+            writeln!(out, "    n{id}[\"{}\"]", name.replace('"', "#quot;"))?;
+        }
+        for (from, to, optional) in &self.edges {
+            let arrow = match optional {
+                false => "-->",
+                true => "-.->",
+            };
+            writeln!(out, "    n{} {arrow} n{}", ids[from], ids[to])?;
+        }
+        Ok(())
+    }
+}
+
+// Other characters, including non-ASCII ones, are allowed as is in DOT strings.
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[test]
+fn query_orphans() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b"], &[]),
+        test_package("b", Reason::Dependency, &[], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+    ]);
+    let app = App::new(&packages);
+    let mut out = Vec::new();
+    run_query(&app, args::Query::Orphans, None, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "c\n");
+}
+
+#[test]
+fn dot_restricted_to_root() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b"], &[]),
+        test_package("b", Reason::Dependency, &["c"], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+        test_package("d", Reason::Explicit, &["c"], &[]),
+    ]);
+    let app = App::new(&packages);
+    let mut out = Vec::new();
+    let graph = DependencyGraph::new(&app, Some("b")).unwrap();
+    graph.write_dot(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "digraph dependencies {\n    \"b\";\n    \"c\";\n    \"b\" -> \"c\";\n}\n"
+    );
+    assert!(DependencyGraph::new(&app, Some("x")).is_err());
+}
+
+#[test]
+fn dot_quoting() {
+    assert_eq!(dot_quote("a"), r#""a""#);
+    assert_eq!(dot_quote("πkg"), r#""πkg""#);
+    assert_eq!(dot_quote(r#"a"b\c"#), r#""a\"b\\c""#);
+}
+
+#[test]
+fn graph_formats() {
+    let mut a = test_package("a&b", Reason::Explicit, &["c"], &[]);
+    a.optional_dependencies = vec![leptohadron::OptionalDependency {
+        name: "d".to_string(),
+        description: None,
+    }];
+    let packages = test_packages([
+        a,
+        test_package("c", Reason::Dependency, &[], &[]),
+        test_package("d", Reason::Dependency, &[], &[]),
+    ]);
+    let app = App::new(&packages);
+    let graph = DependencyGraph::new(&app, None).unwrap();
+    let mut out = Vec::new();
+    graph.write_mermaid(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "graph TD\n    n0[\"a&b\"]\n    n1[\"c\"]\n    n2[\"d\"]\n    n0 --> n1\n    n0 -.-> n2\n"
+    );
+    let mut out = Vec::new();
+    graph.write_graphml(&mut out).unwrap();
+    let graphml = String::from_utf8(out).unwrap();
+    assert!(graphml.contains(r#"<node id="a&amp;b"/>"#));
+    assert!(graphml
+        .contains(r#"<edge source="a&amp;b" target="d"><data key="optional">true</data></edge>"#));
+    assert!(graphml.ends_with("</graphml>\n"));
+}
+
+#[derive(Default)]
+struct Database {
+    packages: BTreeMap<String, PackageDesc>,
+    // One broken package shouldn't prevent looking at the others. The errors and warnings are
+    // printed after leaving the alternate screen.
+    errors: Vec<anyhow::Error>,
+    warnings: Vec<String>,
+    load_time: std::time::Duration,
+}
+
+impl Database {
+    // The packages are read from the cache at `cache` while the database hasn't changed and written
+    // to it otherwise.
+    fn load(backend: &dyn PackageBackend, cache: Option<&std::path::Path>) -> Result<Self> {
+        let start = std::time::Instant::now();
+        let mut self_ = Self::default();
+        let cache = cache.zip(backend.modified());
+        if let Some((path, modified)) = cache {
+            if let Some(packages) = Cache::load(path, backend.location(), modified) {
+                for desc in packages {
+                    self_.insert(desc);
+                }
+                self_.load_time = start.elapsed();
+                return Ok(self_);
+            }
+        }
+        for desc in backend.packages().with_context(|| {
+            format!(
+                "failed to load installed packages from {}",
+                backend.location()
+            )
+        })? {
+            match desc {
+                Ok(desc) => self_.insert(desc),
+                Err(err) => self_.errors.push(err),
+            }
+        }
+        // The cache doesn't keep problems, like duplicate names found by `insert`, which should be
+        // reported every time.
+        if let (Some((path, modified)), true) =
+            (cache, self_.errors.is_empty() && self_.warnings.is_empty())
+        {
+            let packages = self_.packages.values().collect();
+            if let Err(err) = Cache::save(path, backend.location(), modified, packages) {
+                self_
+                    .warnings
+                    .push(format!("failed to save cache: {err:#}"));
+            }
+        }
+        self_.load_time = start.elapsed();
+        Ok(self_)
+    }
+
+    // Of packages with the same name, which only happens in a broken database, the most recently
+    // installed one is kept. Versions aren't compared, so with the same install date the one read
+    // first is kept.
+    fn insert(&mut self, desc: PackageDesc) {
+        for warning in &desc.warnings {
+            self.warnings.push(format!("{}: {warning}", desc.name));
+        }
+        if let Some(existing) = self.packages.get(&desc.name) {
+            self.warnings.push(format!(
+                "{}: installed more than once, versions {} and {}",
+                desc.name, existing.version, desc.version
+            ));
+            if existing.install_date >= desc.install_date {
+                return;
+            }
+        }
+        self.packages.insert(desc.name.clone(), desc);
+    }
+
+    // Prints the problems of loading and of the session in `log`. Without `verbose` only how many
+    // there were is printed.
+    fn report(&self, log: &[String], verbose: bool, out: &mut impl std::io::Write) -> Result<()> {
+        if !verbose {
+            let count = self.errors.len() + self.warnings.len() + log.len();
+            if count > 0 {
+                writeln!(
+                    out,
+                    "{} packages skipped, {count} problems in total, run with --verbose to see them",
+                    self.errors.len()
+                )?;
+            }
+            return Ok(());
+        }
+        for err in &self.errors {
+            writeln!(out, "skipped package: {err:#}")?;
+        }
+        for warning in &self.warnings {
+            writeln!(out, "warning: {warning}")?;
+        }
+        for message in log {
+            writeln!(out, "{message}")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn report_problems() {
+    let mut database = Database::default();
+    let mut out = Vec::new();
+    database.report(&[], false, &mut out).unwrap();
+    assert!(out.is_empty());
+    database.errors.push(anyhow::anyhow!("broken"));
+    database.warnings.push("a: odd".to_string());
+    let log = ["failed to open url".to_string()];
+    database.report(&log, false, &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(std::mem::take(&mut out)).unwrap(),
+        "1 packages skipped, 3 problems in total, run with --verbose to see them\n"
+    );
+    database.report(&log, true, &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "skipped package: broken\nwarning: a: odd\nfailed to open url\n"
+    );
+}
+
+#[test]
+fn load_from_cache() {
+    let root = std::env::temp_dir().join(format!("leptohadron-cached-{}", std::process::id()));
+    let desc = root.join("local/a-1/desc");
+    std::fs::create_dir_all(desc.parent().unwrap()).unwrap();
+    std::fs::write(&desc, "%NAME%\na\n\n%VERSION%\n1\n\n").unwrap();
+    let modified = std::fs::metadata(&desc).unwrap().modified().unwrap();
+    let backend = leptohadron::Pacman {
+        dbpath: root.join("local").to_str().unwrap().to_string(),
+    };
+    let cache = root.join("cache/packages.json");
+    let database = Database::load(&backend, Some(&cache)).unwrap();
+    assert!(database.packages.contains_key("a"));
+    // unchanged modification time means the cache is used instead of the changed file
+    std::fs::write(&desc, "%NAME%\nb\n\n%VERSION%\n1\n\n").unwrap();
+    let file = std::fs::File::options().write(true).open(&desc).unwrap();
+    file.set_modified(modified).unwrap();
+    let database = Database::load(&backend, Some(&cache)).unwrap();
+    assert!(database.packages.contains_key("a"));
+    let database = Database::load(&backend, None).unwrap();
+    assert!(database.packages.contains_key("b"));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn duplicates_not_cached() {
+    let root = std::env::temp_dir().join(format!("leptohadron-duplicates-{}", std::process::id()));
+    for version in ["1", "2"] {
+        let desc = root.join(format!("local/a-{version}/desc"));
+        std::fs::create_dir_all(desc.parent().unwrap()).unwrap();
+        std::fs::write(&desc, format!("%NAME%\na\n\n%VERSION%\n{version}\n\n")).unwrap();
+    }
+    let backend = leptohadron::Pacman {
+        dbpath: root.join("local").to_str().unwrap().to_string(),
+    };
+    let cache = root.join("cache/packages.json");
+    for _ in 0..2 {
+        let database = Database::load(&backend, Some(&cache)).unwrap();
+        assert_eq!(database.warnings.len(), 1);
+        assert!(!cache.exists());
+    }
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn restore_settings() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &[], &[]),
+        test_package("b", Reason::Explicit, &["c"], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.event(key(KeyCode::Char('j')));
+    app.event(key(KeyCode::Char('R')));
+    app.event(key(KeyCode::Char('l')));
+    assert!(app.event(key(KeyCode::Char('r'))));
+    assert!(app.reload);
+    let settings = app.settings();
+
+    let mut app = App::new(&packages);
+    app.restore(settings);
+    assert_eq!(app.columns[1].selected().unwrap().name, "b");
+    assert_eq!(app.columns[2].title, "Would also remove");
+    assert_eq!(app.columns[2].selected().unwrap().name, "c");
+    assert_eq!(app.active_column, 2);
+    assert!(app.columns[2].is_active && !app.columns[1].is_active);
+}
+
+// Opens the url in the default browser without waiting for it.
+fn open_url(url: &str) -> Result<()> {
+    // The browser must not write to the terminal that is in raw mode.
+    let mut child = std::process::Command::new("xdg-open")
+        .arg(url)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .context("spawn xdg-open")?;
+    // reap the process when it exits
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+// Whether the event changed the database. Reading it, like this program does when loading, causes
+// access events that have to be ignored or every reload would cause another one.
+fn is_database_change(event: &notify::Result<notify::Event>) -> bool {
+    use notify::event::{EventKind, ModifyKind};
+    match event {
+        Ok(event) => matches!(
+            event.kind,
+            EventKind::Create(_)
+                | EventKind::Modify(
+                    ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Metadata(_)
+                )
+                | EventKind::Remove(_)
+        ),
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn database_changes_ignore_access() {
+    use notify::event::{AccessKind, CreateKind, DataChange, EventKind, ModifyKind};
+    let event = |kind| Ok(notify::Event::new(kind));
+    assert!(!is_database_change(&event(EventKind::Access(
+        AccessKind::Any
+    ))));
+    assert!(is_database_change(&event(EventKind::Create(
+        CreateKind::File
+    ))));
+    assert!(is_database_change(&event(EventKind::Modify(
+        ModifyKind::Data(DataChange::Content)
+    ))));
+    assert!(!is_database_change(&Err(notify::Error::generic(
+        "watch failed"
+    ))));
+}
+
+// Whether a change of the database is complete. Package managers change many files in one
+// transaction and hold a lock while doing so.
+fn database_settled(backend: &dyn PackageBackend, changed: std::time::Instant) -> bool {
+    let quiet = changed.elapsed() >= std::time::Duration::from_millis(500);
+    quiet && !backend.locked()
+}
+
+#[test]
+fn database_settled_waits_for_lock() {
+    let root = std::env::temp_dir().join(format!("leptohadron-lock-{}", std::process::id()));
+    let path = root.join("local");
+    std::fs::create_dir_all(&path).unwrap();
+    let backend = Pacman {
+        dbpath: path.to_str().unwrap().to_string(),
+    };
+    let long_ago = std::time::Instant::now() - std::time::Duration::from_secs(10);
+    assert!(!database_settled(&backend, std::time::Instant::now()));
+    assert!(database_settled(&backend, long_ago));
+    std::fs::write(root.join("db.lck"), "").unwrap();
+    assert!(!database_settled(&backend, long_ago));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn peek_and_return() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b", "c"], &[]),
+        test_package("b", Reason::Dependency, &["d"], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+        test_package("d", Reason::Dependency, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    let main = |app: &App| app.columns[1].selected().unwrap().name.clone();
+    app.event(key(KeyCode::Char('l')));
+    app.event(key(KeyCode::Char('p')));
+    assert_eq!(main(&app), "b");
+    assert_eq!(app.active_column, 2);
+    // keep peeking from the dependencies of the peeked package
+    app.event(key(KeyCode::Char('p')));
+    assert_eq!(main(&app), "d");
+    app.event(key(KeyCode::Char('P')));
+    assert_eq!(main(&app), "b");
+    app.event(key(KeyCode::Char('P')));
+    assert_eq!(main(&app), "a");
+    assert_eq!(app.active_column, 2);
+    assert_eq!(app.columns[2].selected().unwrap().name, "b");
+    // the explicit filter removed for peeking is back
+    assert_eq!(app.columns[1].packages.len(), 1);
+    // peeking isn't part of the history
+    assert!(app.back.is_empty());
+    app.event(key(KeyCode::Char('P')));
+    assert_eq!(main(&app), "a");
+}
+
+#[test]
+fn breadcrumb_trail() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b"], &[]),
+        test_package("b", Reason::Dependency, &["c"], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+        test_package("d", Reason::Explicit, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.show_help = false;
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+    let top_row = |app: &mut App, terminal: &mut Terminal<tui::backend::TestBackend>| {
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let row: String = (0..90).map(|x| buffer.get(x, 0).symbol.as_str()).collect();
+        row.trim_end().to_string()
+    };
+    let trail = |app: &App| -> Vec<String> {
+        app.trail
+            .iter()
+            .map(|package| package.name.clone())
+            .collect()
+    };
+    assert!(top_row(&mut app, &mut terminal).starts_with('┌'));
+    for _ in 0..2 {
+        app.event(key(KeyCode::Char('l')));
+        app.event(key(KeyCode::Enter));
+    }
+    assert_eq!(top_row(&mut app, &mut terminal), "a › b › c");
+    app.event(key(KeyCode::Backspace));
+    assert_eq!(trail(&app), ["a", "b"]);
+    app.event(key(KeyCode::Char(']')));
+    assert_eq!(trail(&app), ["a", "b", "c"]);
+    // too long for the terminal
+    let mut terminal_small = Terminal::new(tui::backend::TestBackend::new(40, 30)).unwrap();
+    app.trail = vec![&packages["b"]; 20];
+    terminal_small.draw(|frame| app.draw(frame)).unwrap();
+    let buffer = terminal_small.backend().buffer();
+    let row: String = (0..40).map(|x| buffer.get(x, 0).symbol.as_str()).collect();
+    assert!(row.starts_with('…') && row.ends_with("b › b"));
+    app.trail = vec![&packages["a"], &packages["b"], &packages["c"]];
+    // moving in the center list isn't following dependencies
+    app.event(key(KeyCode::Char('h')));
+    app.event(key(KeyCode::Char('j')));
+    assert!(trail(&app).is_empty());
+    assert!(top_row(&mut app, &mut terminal).starts_with('┌'));
+}
+
+#[test]
+fn sort_all_columns() {
+    let mut packages = [
+        test_package("a", Reason::Explicit, &["b", "c"], &[]),
+        test_package("b", Reason::Dependency, &[], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+        test_package("d", Reason::Explicit, &[], &[]),
+    ];
+    packages[2].size = Some(100);
+    packages[3].size = Some(100);
+    let packages = test_packages(packages);
+    let mut app = App::new(&packages);
+    let names = |app: &App, column: usize| -> Vec<String> {
+        app.columns[column]
+            .packages
+            .iter()
+            .map(|package| package.name.clone())
+            .collect()
+    };
+    assert_eq!(names(&app, 2), ["b", "c"]);
+    app.event(key(KeyCode::Char('s')));
+    assert!(app
+        .columns
+        .iter()
+        .all(|c| c.sort_criteria == SortCritera::SizeDesc));
+    assert_eq!(names(&app, 1), ["d", "a"]);
+    assert_eq!(names(&app, 2), ["c", "b"]);
+
+    let mut app = App::new(&packages);
+    app.per_column_sort = true;
+    app.event(key(KeyCode::Char('s')));
+    assert_eq!(names(&app, 1), ["d", "a"]);
+    assert_eq!(names(&app, 2), ["b", "c"]);
+    // the next sorting for all columns doesn't depend on the active one
+    app.per_column_sort = false;
+    app.event(key(KeyCode::Char('s')));
+    assert!(app
+        .columns
+        .iter()
+        .all(|c| c.sort_criteria == SortCritera::SizeDesc));
+}
+
+#[test]
+fn sort_keeps_fuzzy_order() {
+    let mut packages = [
+        test_package("ab", Reason::Explicit, &[], &[]),
+        test_package("axb", Reason::Explicit, &[], &[]),
+    ];
+    packages[1].size = Some(100);
+    let packages = test_packages(packages);
+    let mut app = App::new(&packages);
+    app.search_mode = SearchMode::Fuzzy;
+    app.set_filter(Filter::Search("ab".to_string()), true);
+    app.event(key(KeyCode::Char('s')));
+    assert_eq!(app.columns[1].sort_criteria, SortCritera::SizeDesc);
+    assert_eq!(app.columns[1].packages[0].name, "ab");
+}
+
+#[test]
+fn navigation_history() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b"], &[]),
+        test_package("b", Reason::Dependency, &["c"], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    let main = |app: &App| app.columns[1].selected().unwrap().name.clone();
+    for _ in 0..2 {
+        app.event(key(KeyCode::Char('l')));
+        app.event(key(KeyCode::Enter));
+    }
+    assert_eq!(main(&app), "c");
+    app.event(key(KeyCode::Backspace));
+    assert_eq!(main(&app), "b");
+    assert_eq!(app.columns[2].packages[0].name, "c");
+    app.event(key(KeyCode::Backspace));
+    assert_eq!(main(&app), "a");
+    // nothing further back
+    app.event(key(KeyCode::Backspace));
+    assert_eq!(main(&app), "a");
+    app.event(key(KeyCode::Char(']')));
+    assert_eq!(main(&app), "b");
+}
+
+#[test]
+fn seek_by_prefix() {
+    let packages = test_packages(
+        ["alsa", "bash", "dbus", "dcc", "zsh"]
+            .map(|name| test_package(name, Reason::Explicit, &[], &[])),
+    );
+    let mut app = App::new(&packages);
+    let main = |app: &App| app.columns[1].selected().unwrap().name.clone();
+    app.event(key(KeyCode::Char('d')));
+    assert_eq!(main(&app), "dbus");
+    app.event(key(KeyCode::Char('c')));
+    assert_eq!(main(&app), "dcc");
+    // keys with an action run it instead of continuing the prefix
+    app.event(key(KeyCode::Char('j')));
+    assert_eq!(main(&app), "zsh");
+    // after the timeout the prefix starts over, wrapping around
+    app.seek_time = std::time::Instant::now().checked_sub(SEEK_TIMEOUT);
+    app.event(key(KeyCode::Char('b')));
+    assert_eq!(main(&app), "bash");
+    app.event(key(KeyCode::Char('a')));
+    assert_eq!(main(&app), "bash");
+    app.seek_time = std::time::Instant::now().checked_sub(SEEK_TIMEOUT);
+    app.event(key(KeyCode::Char('a')));
+    assert_eq!(main(&app), "alsa");
+}
+
+#[test]
+fn open_url_without_url() {
+    let packages = test_packages([test_package("a", Reason::Explicit, &[], &[])]);
+    let mut app = App::new(&packages);
+    app.event(key(KeyCode::Char('w')));
+    assert_eq!(app.message.as_deref(), Some("a has no url"));
+}
+
+#[test]
+fn render_only_visible_entries() {
+    let packages = test_packages(
+        (0..10_000).map(|i| test_package(&format!("{i:05}"), Reason::Explicit, &[], &[])),
+    );
+    let mut app = App::new(&packages);
+    app.show_help = false;
+    app.event(key(KeyCode::Char('0')));
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(90, 30)).unwrap();
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    let area = app.columns[1].list_area;
+    let buffer = terminal.backend().buffer();
+    let last_row: String = (area.left()..area.left() + 5)
+        .map(|column| buffer.get(column, area.bottom() - 1).symbol.clone())
+        .collect();
+    assert_eq!(last_row, "09999");
+    assert_eq!(app.columns[1].offset, 10_000 - area.height as usize);
+}
+
+// Draws the app and compares the text on the screen to `src/snapshots/{name}.txt`. With
+// `UPDATE_SNAPSHOTS` set the file is written instead, after which the difference can be reviewed
+// with git.
+#[cfg(test)]
+fn assert_snapshot(name: &str, app: &mut App, width: u16, height: u16) {
+    let mut terminal = Terminal::new(tui::backend::TestBackend::new(width, height)).unwrap();
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    let buffer = terminal.backend().buffer();
+    let mut screen = String::new();
+    for y in 0..height {
+        let row: String = (0..width)
+            .map(|x| buffer.get(x, y).symbol.as_str())
+            .collect();
+        screen.push_str(row.trim_end());
+        screen.push('\n');
+    }
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/snapshots")
+        .join(format!("{name}.txt"));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, screen).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("{err}: run with UPDATE_SNAPSHOTS=1 to create {path:?}"));
+    assert!(
+        screen == expected,
+        "{name} differs from {path:?}, run with UPDATE_SNAPSHOTS=1 to update it:\n{screen}"
+    );
+}
+
+// A few packages with everything the interface shows except install dates, which depend on the
+// time zone.
+#[cfg(test)]
+fn snapshot_packages() -> BTreeMap<String, PackageDesc> {
+    let mut packages = [
+        test_package("firefox", Reason::Explicit, &["gtk3", "nss>=3.90"], &[]),
+        test_package("gtk3", Reason::Dependency, &["glib2"], &[]),
+        test_package("glib2", Reason::Dependency, &[], &[]),
+        test_package("nss", Reason::Dependency, &[], &[]),
+        test_package("vim", Reason::Explicit, &[], &[]),
+    ];
+    for (i, package) in packages.iter_mut().enumerate() {
+        package.version = format!("{}.0-1", i + 1);
+        package.description = Some(format!("the {} package", package.name));
+        package.size = Some((i as u64 + 1) * 3_000_000);
+    }
+    packages[0].url = Some("https://www.mozilla.org/firefox/".to_string());
+    packages[0].groups = vec!["browsers".to_string()];
+    test_packages(packages)
+}
+
+#[test]
+fn snapshot_default_view() {
+    let packages = snapshot_packages();
+    let mut app = App::new(&packages);
+    assert_snapshot("default_view", &mut app, 100, 60);
+}
+
+#[test]
+fn snapshot_help_hidden() {
+    let packages = snapshot_packages();
+    let mut app = App::new(&packages);
+    app.event(key(KeyCode::Char('?')));
+    assert_snapshot("help_hidden", &mut app, 100, 30);
+}
+
+#[test]
+fn snapshot_search() {
+    let packages = snapshot_packages();
+    let mut app = App::new(&packages);
+    app.show_help = false;
+    app.set_filter(Filter::Explicit, false);
+    for char in "/gl".chars() {
+        app.event(key(KeyCode::Char(char)));
+    }
+    assert_snapshot("search", &mut app, 100, 30);
+}
+
+#[test]
+fn snapshot_details() {
+    let packages = snapshot_packages();
+    let mut app = App::new(&packages);
+    app.show_help = false;
+    app.event(key(KeyCode::Char('l')));
+    app.event(key(KeyCode::Char('j')));
+    assert_snapshot("details", &mut app, 100, 30);
+}
+
+#[test]
+fn filter_keeps_selection() {
+    let packages = test_packages([
+        test_package("a", Reason::Dependency, &[], &[]),
+        test_package("b", Reason::Explicit, &[], &[]),
+        test_package("c", Reason::Explicit, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.event(key(KeyCode::Char('j')));
+    assert_eq!(app.columns[1].selected().unwrap().name, "c");
+    app.event(key(KeyCode::Char('e')));
+    assert_eq!(app.columns[1].packages.len(), 3);
+    assert_eq!(app.columns[1].selected().unwrap().name, "c");
+    app.event(key(KeyCode::Char('e')));
+    assert_eq!(app.columns[1].selected().unwrap().name, "c");
+}
+
+#[test]
+fn duplicate_package_names() {
+    let package = |version: &str, install_date| PackageDesc {
+        name: "a".to_string(),
+        version: version.to_string(),
+        install_date: Some(install_date),
+        ..Default::default()
+    };
+    for order in [[1, 2], [2, 1]] {
+        let mut database = Database::default();
+        for install_date in order {
+            database.insert(package(&install_date.to_string(), install_date));
+        }
+        assert_eq!(database.packages["a"].version, "2");
+        assert_eq!(database.warnings.len(), 1);
+    }
+    // the one read first, comparing the versions as text would keep 1.9
+    let mut database = Database::default();
+    database.insert(package("1.10", 1));
+    database.insert(package("1.9", 1));
+    assert_eq!(database.packages["a"].version, "1.10");
+}
+
+#[test]
+fn search_in_active_column() {
+    let packages = test_packages([
+        test_package("a", Reason::Explicit, &["b", "c"], &[]),
+        test_package("b", Reason::Dependency, &[], &[]),
+        test_package("c", Reason::Dependency, &[], &[]),
+    ]);
+    let mut app = App::new(&packages);
+    app.event(key(KeyCode::Char('l')));
+    for code in [KeyCode::Char('/'), KeyCode::Char('c'), KeyCode::Enter] {
+        app.event(key(code));
+    }
+    assert_eq!(app.active_column, 2);
+    assert_eq!(app.columns[2].selected().unwrap().name, "c");
+    assert_eq!(app.columns[1].selected().unwrap().name, "a");
+    app.event(key(KeyCode::Char('n')));
+    assert_eq!(app.message.as_deref(), Some("match 1/1 for \"c\""));
+    for code in [KeyCode::Char('/'), KeyCode::Char('x'), KeyCode::Enter] {
+        app.event(key(code));
+    }
+    assert_eq!(app.message.as_deref(), Some("no match for \"x\""));
+    app.event(key(KeyCode::Char('j')));
+    assert_eq!(app.message, None);
+}
+
+fn package_backend(args: &args::Args) -> Box<dyn PackageBackend> {
+    match args.backend {
+        args::Backend::Pacman => Box::new(Pacman {
+            dbpath: args.dbpath.clone(),
+        }),
+        args::Backend::Dpkg => Box::new(Dpkg {
+            dbpath: args.dbpath.clone(),
+        }),
+    }
+}
+
+fn main() -> Result<()> {
+    let args = args::Args::parse(
+        std::env::args().skip(1),
+        std::path::Path::new(args::PACMAN_CONF),
+    )?;
+    let path = args.dbpath.as_str();
+    let cache_path = Cache::path();
+    let mut database = Database::load(&*package_backend(&args), cache_path.as_deref())?;
+    if let Some(query) = args.query {
+        database.report(&[], args.verbose, &mut std::io::stderr())?;
+        return run_query(
+            &App::new(&database.packages),
+            query,
+            args.root.as_deref(),
+            &mut std::io::stdout().lock(),
+        );
+    }
+
+    // stdout is kept for the picked package
+    let mut output: Box<dyn std::io::Write> = match args.print_selection {
+        true => Box::new(std::io::stderr()),
+        false => Box::new(std::io::stdout()),
+    };
+    crossterm::terminal::enable_raw_mode().context("enable_raw_mode")?;
+    crossterm::execute!(output, EnterAlternateScreen).context("EnterAlternateScreen")?;
+    crossterm::execute!(output, EnableMouseCapture).context("EnableMouseCapture")?;
+    let backend = tui::backend::CrosstermBackend::new(output);
+    let mut terminal = Terminal::new(backend).context("Terminal::new")?;
+
+    // Pacman changing the database triggers a reload. Without the watcher only manual reloading
+    // works.
+    let (sender, changes) = std::sync::mpsc::channel();
+    let watcher = notify::recommended_watcher(move |event| {
+        if is_database_change(&event) {
+            // The receiver only goes away when quitting.
+            let _ = sender.send(());
+        }
+    })
+    .and_then(|mut watcher| {
+        notify::Watcher::watch(
+            &mut watcher,
+            std::path::Path::new(path),
+            notify::RecursiveMode::Recursive,
+        )?;
+        Ok(watcher)
+    });
+    // last change that hasn't been reloaded yet
+    let mut changed: Option<std::time::Instant> = None;
+
+    let config = Config::path().as_deref().map(Config::load);
+    let config_error = match &config {
+        Some(Err(err)) => Some(format!("using default config: {err:#}")),
+        _ => None,
+    };
+    // collected over reloads, printed after quitting
+    let mut log: Vec<String> = Vec::new();
+    let config = match config {
+        Some(Ok(config)) => config,
+        _ => Config::default(),
+    };
+    let preferences_path = Preferences::path();
+    let mut preferences = preferences_path.as_deref().map(Preferences::load);
+
+    // from before reloading
+    let mut settings: Option<Settings> = None;
+    // captured before the app goes away
+    let mut picked: Option<String> = None;
+    let result = 'reload: loop {
+        let mut app = App::new(&database.packages);
+        app.scroll_step = args.scroll_step;
+        app.backend = package_backend(&args);
+        app.load_time = database.load_time;
+        app.skipped = database.errors.len();
+        app.keymap = config.keymap.clone();
+        app.confirm_quit = config.confirm_quit;
+        app.page_size = config.page_size;
+        app.relative_dates = config.relative_dates;
+        app.per_column_sort = config.per_column_sort;
+        app.colors =
+            !args.no_color && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+        app.theme = args.theme.unwrap_or_else(detect_theme);
+        if let Some(settings) = settings.take() {
+            app.restore(settings);
+            app.message = Some(format!("reloaded {} packages", database.packages.len()));
+        } else {
+            match preferences.take() {
+                Some(Ok(preferences)) => app.apply_preferences(preferences),
+                Some(Err(err)) => app.log.push(format!("using default settings: {err:#}")),
+                None => (),
+            }
+            if let Err(err) = &watcher {
+                app.log
+                    .push(format!("not watching the database for changes: {err}"));
+            }
+            app.log.extend(config_error.clone());
+            // the status bar only has room for the last one
+            app.message = app.log.last().cloned();
+        }
+        let mut redraw = true;
+        loop {
+            if redraw {
+                match terminal.draw(|frame| app.draw(frame)) {
+                    Ok(_) => (),
+                    Err(err) => break 'reload Err(err).context("draw"),
+                }
+            }
+            // wake up regularly to check for changes of the database
+            redraw = match crossterm::event::poll(std::time::Duration::from_millis(100)) {
+                Ok(redraw) => redraw,
+                Err(err) => break 'reload Err(err).context("crossterm::event::poll"),
+            };
+            if redraw {
+                let event = match crossterm::event::read() {
+                    Ok(event) => event,
+                    Err(err) => break 'reload Err(err).context("crossterm::event::read"),
+                };
+                if app.event(event) {
+                    break;
+                }
+            }
+            if changes.try_iter().count() > 0 {
+                changed = Some(std::time::Instant::now());
+            }
+            if changed.is_some_and(|changed| database_settled(&*app.backend, changed)) {
+                app.reload = true;
+                break;
+            }
+        }
+        log.append(&mut app.log);
+        if !app.reload {
+            picked = app.picked().map(str::to_string);
+            if let Some(path) = &preferences_path {
+                if let Err(err) = app.preferences().save(path) {
+                    log.push(format!("failed to save settings: {err:#}"));
+                }
+            }
+            break Ok(());
+        }
+        settings = Some(app.settings());
+        changed = None;
+        database = match Database::load(&*app.backend, cache_path.as_deref()) {
+            Ok(database) => database,
+            Err(err) => break Err(err),
+        };
+        // already part of what was loaded
+        changes.try_iter().count();
+    };
+
+    crossterm::execute!(terminal.backend_mut(), DisableMouseCapture)
+        .context("DisableMouseCapture")?;
+    crossterm::execute!(terminal.backend_mut(), LeaveAlternateScreen)
+        .context("LeaveAlternateScreen")?;
+    crossterm::terminal::disable_raw_mode().context("disable_raw_mode")?;
+    terminal.show_cursor().context("show_cursor")?;
+
+    database.report(&log, args.verbose, &mut std::io::stderr())?;
+    if let (true, Some(picked), Ok(())) = (args.print_selection, picked, &result) {
+        println!("{picked}");
+    }
     result
 }
@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    search::{SearchMode, SearchScope},
+    DetailsLayout, Filter, SortCritera,
+};
+
+// View settings that are kept between sessions. Missing fields use the defaults so that old state
+// files keep working.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Preferences {
+    pub sort_criteria: [SortCritera; 3],
+    pub show_help: bool,
+    pub show_files: bool,
+    pub details_layout: DetailsLayout,
+    pub filters: Vec<Filter>,
+    pub binary_units: bool,
+    pub separate_optional: bool,
+    pub removal_mode: bool,
+    pub case_sensitive: bool,
+    pub search_mode: SearchMode,
+    pub search_scope: SearchScope,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            sort_criteria: Default::default(),
+            show_help: true,
+            show_files: false,
+            details_layout: Default::default(),
+            filters: vec![Filter::Explicit],
+            binary_units: true,
+            separate_optional: false,
+            removal_mode: false,
+            case_sensitive: false,
+            search_mode: Default::default(),
+            search_scope: Default::default(),
+        }
+    }
+}
+
+impl Preferences {
+    pub fn path() -> Option<PathBuf> {
+        crate::xdg::path("XDG_STATE_HOME", ".local/state", "state.toml")
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        crate::xdg::load_toml(path, |contents| Ok(toml::from_str(contents)?))
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context(format!("create {parent:?}"))?;
+        }
+        let contents = toml::to_string(self).context("serialize")?;
+        std::fs::write(path, contents).context(format!("write {path:?}"))
+    }
+}
+
+#[test]
+fn preferences_round_trip() {
+    let path = std::env::temp_dir().join(format!(
+        "leptohadron-state-{}/state.toml",
+        std::process::id()
+    ));
+    assert_eq!(Preferences::load(&path).unwrap(), Preferences::default());
+    let preferences = Preferences {
+        sort_criteria: [
+            SortCritera::NameAsc,
+            SortCritera::SizeDesc,
+            SortCritera::DependantsDesc,
+        ],
+        show_help: false,
+        details_layout: DetailsLayout::Bottom,
+        filters: vec![Filter::Group("gnome".to_string()), Filter::MinSize(1000)],
+        search_mode: SearchMode::Regex,
+        ..Default::default()
+    };
+    preferences.save(&path).unwrap();
+    assert_eq!(Preferences::load(&path).unwrap(), preferences);
+    std::fs::write(&path, "show_help = 1").unwrap();
+    assert!(Preferences::load(&path).is_err());
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,460 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use leptohadron::PackageDesc;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum SearchMode {
+    // text contains the term
+    #[default]
+    Substring,
+    // text contains the characters of the term in order, best matches first
+    Fuzzy,
+    // text matches the term as a regular expression
+    Regex,
+}
+
+impl SearchMode {
+    pub fn next(&self) -> Self {
+        match self {
+            SearchMode::Substring => SearchMode::Fuzzy,
+            SearchMode::Fuzzy => SearchMode::Regex,
+            SearchMode::Regex => SearchMode::Substring,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SearchMode::Substring => "substring",
+            SearchMode::Fuzzy => "fuzzy",
+            SearchMode::Regex => "regex",
+        }
+    }
+}
+
+// which fields of a package are searched
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum SearchScope {
+    #[default]
+    Name,
+    NameDescription,
+    All,
+}
+
+impl SearchScope {
+    pub fn next(&self) -> Self {
+        match self {
+            SearchScope::Name => SearchScope::NameDescription,
+            SearchScope::NameDescription => SearchScope::All,
+            SearchScope::All => SearchScope::Name,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SearchScope::Name => "name",
+            SearchScope::NameDescription => "name+description",
+            SearchScope::All => "all fields",
+        }
+    }
+
+    pub fn includes_description(&self) -> bool {
+        !matches!(self, SearchScope::Name)
+    }
+
+    pub fn includes_url(&self) -> bool {
+        matches!(self, SearchScope::All)
+    }
+
+    fn fields<'a>(&self, package: &'a PackageDesc) -> impl Iterator<Item = &'a str> {
+        [
+            Some(package.name.as_str()),
+            self.includes_description()
+                .then_some(package.description.as_deref())
+                .flatten(),
+            self.includes_url()
+                .then_some(package.url.as_deref())
+                .flatten(),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+enum Pattern {
+    // lowercase when not case sensitive
+    Substring(String),
+    Fuzzy(String),
+    Regex(Regex),
+}
+
+pub struct Matcher {
+    // the settings the matcher was created with
+    term: String,
+    mode: SearchMode,
+    case_sensitive: bool,
+    // all have to match
+    patterns: Vec<Pattern>,
+    // none may match
+    excluded: Vec<Pattern>,
+}
+
+impl Matcher {
+    // Fails if the term is not a valid regex in regex mode. Substring and fuzzy terms are split on
+    // whitespace into words that all have to match, except words starting with `-` or `!` which
+    // must not match. A regex is used as a whole because spaces can be part of it.
+    pub fn new(term: &str, mode: SearchMode, case_sensitive: bool) -> Result<Self, regex::Error> {
+        let folded = match case_sensitive {
+            true => term.to_string(),
+            false => term.to_lowercase(),
+        };
+        let mut words = Vec::new();
+        let mut excluded_words = Vec::new();
+        for word in folded.split_whitespace() {
+            match word
+                .strip_prefix(['-', '!'])
+                .filter(|word| !word.is_empty())
+            {
+                Some(word) => excluded_words.push(word.to_string()),
+                None => words.push(word.to_string()),
+            }
+        }
+        let pattern = |word: String| match mode {
+            SearchMode::Fuzzy => Pattern::Fuzzy(word),
+            _ => Pattern::Substring(word),
+        };
+        let (patterns, excluded) = match mode {
+            SearchMode::Substring | SearchMode::Fuzzy => (
+                words.into_iter().map(pattern).collect(),
+                excluded_words.into_iter().map(pattern).collect(),
+            ),
+            SearchMode::Regex => (
+                vec![Pattern::Regex(
+                    RegexBuilder::new(term)
+                        .case_insensitive(!case_sensitive)
+                        .build()?,
+                )],
+                Vec::new(),
+            ),
+        };
+        Ok(Self {
+            term: term.to_string(),
+            mode,
+            case_sensitive,
+            patterns,
+            excluded,
+        })
+    }
+
+    // whether the matcher was created with these settings
+    pub fn is_for(&self, term: &str, mode: SearchMode, case_sensitive: bool) -> bool {
+        self.term == term && self.mode == mode && self.case_sensitive == case_sensitive
+    }
+
+    // Returns the sum of the best scores of every word among the package's fields in the scope.
+    // Different words can match different fields and excluded words must not match any.
+    pub fn score_package(&self, package: &PackageDesc, scope: SearchScope) -> Option<i64> {
+        let fields: Vec<(&str, String)> = scope
+            .fields(package)
+            .map(|field| (field, self.fold(field).0))
+            .collect();
+        let excluded = self.excluded.iter().any(|pattern| {
+            fields
+                .iter()
+                .any(|(field, folded)| pattern.score(field, folded).is_some())
+        });
+        if excluded {
+            return None;
+        }
+        self.patterns
+            .iter()
+            .map(|pattern| {
+                fields
+                    .iter()
+                    .filter_map(|(field, folded)| pattern.score(field, folded))
+                    .max()
+            })
+            .sum()
+    }
+
+    // Returns a score if every word matches the text. Higher scores are better matches. All
+    // substring and regex matches score the same.
+    pub fn score(&self, text: &str) -> Option<i64> {
+        let lowercase;
+        let folded = match self.case_sensitive {
+            true => text,
+            false => {
+                lowercase = text.to_lowercase();
+                lowercase.as_str()
+            }
+        };
+        if self
+            .excluded
+            .iter()
+            .any(|pattern| pattern.score(text, folded).is_some())
+        {
+            return None;
+        }
+        self.patterns
+            .iter()
+            .map(|pattern| pattern.score(text, folded))
+            .sum()
+    }
+
+    // Returns the sorted byte ranges of `text` that match any word that isn't excluded, for
+    // highlighting.
+    pub fn match_ranges(&self, text: &str) -> Vec<Range<usize>> {
+        let (folded, offsets) = self.fold(text);
+        let original = |range: Range<usize>| offsets[range.start]..offsets[range.end];
+        let mut ranges: Vec<Range<usize>> = self
+            .patterns
+            .iter()
+            .flat_map(|pattern| match pattern {
+                Pattern::Substring(term) => folded
+                    .find(term.as_str())
+                    .map(|start| original(start..start + term.len()))
+                    .into_iter()
+                    .collect(),
+                Pattern::Fuzzy(term) => fuzzy_match(term.as_str(), folded.as_str())
+                    .map(|(_, positions)| positions)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|start| {
+                        // Unwrap because the position is the start of a character.
+                        let len = folded[start..].chars().next().unwrap().len_utf8();
+                        original(start..start + len)
+                    })
+                    .collect(),
+                Pattern::Regex(regex) => regex
+                    .find(text)
+                    .filter(|match_| !match_.is_empty())
+                    .map(|match_| match_.range())
+                    .into_iter()
+                    .collect::<Vec<_>>(),
+            })
+            .collect();
+        // words can match the same characters
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::new();
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    // Returns the text as it is matched against the term and for every byte of it the offset of the
+    // character in the original text that it came from, plus the length of the original text.
+    fn fold(&self, text: &str) -> (String, Vec<usize>) {
+        let mut folded = String::new();
+        let mut offsets = Vec::new();
+        for (offset, char) in text.char_indices() {
+            let len = folded.len();
+            match self.case_sensitive {
+                true => folded.push(char),
+                false => folded.extend(char.to_lowercase()),
+            }
+            offsets.extend(std::iter::repeat_n(offset, folded.len() - len));
+        }
+        offsets.push(text.len());
+        (folded, offsets)
+    }
+}
+
+impl Pattern {
+    // `folded` is `text` lowercased when not case sensitive.
+    fn score(&self, text: &str, folded: &str) -> Option<i64> {
+        match self {
+            Pattern::Substring(term) => folded.contains(term.as_str()).then_some(0),
+            Pattern::Fuzzy(term) => fuzzy_match(term.as_str(), folded).map(|(score, _)| score),
+            Pattern::Regex(regex) => regex.is_match(text).then_some(0),
+        }
+    }
+}
+
+// Returns a score and the byte offsets of the matched characters if the characters of `pattern`
+// appear in `text` in order. Consecutive matches and matches at the start of words score higher,
+// skipped characters lower the score.
+fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let text: Vec<(usize, char)> = text.char_indices().collect();
+    let mut score = 0;
+    let mut positions = Vec::new();
+    // index in `text` after the previous match
+    let mut position = 0;
+    for char in pattern.chars() {
+        let index = position + text[position..].iter().position(|(_, c)| *c == char)?;
+        score += 1;
+        if position > 0 && index == position {
+            score += 4;
+        }
+        if index == 0 || matches!(text[index - 1].1, '-' | '_' | '.' | ' ') {
+            score += 2;
+        }
+        if position > 0 {
+            score -= (index - position) as i64;
+        }
+        positions.push(text[index].0);
+        position = index + 1;
+    }
+    Some((score, positions))
+}
+
+#[cfg(test)]
+fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    fuzzy_match(pattern, text).map(|(score, _)| score)
+}
+
+#[test]
+fn fuzzy_score_matches_subsequence() {
+    assert!(fuzzy_score("frfx", "firefox").is_some());
+    assert!(fuzzy_score("xf", "firefox").is_none());
+    assert!(fuzzy_score("", "firefox").is_some());
+}
+
+#[test]
+fn fuzzy_score_prefers_consecutive() {
+    let firefox = fuzzy_score("fire", "firefox").unwrap();
+    let other = fuzzy_score("fire", "f-i-r-e").unwrap();
+    assert!(firefox > other);
+}
+
+#[test]
+fn matcher_case_sensitivity() {
+    let matches = |term, mode, case_sensitive| {
+        Matcher::new(term, mode, case_sensitive)
+            .unwrap()
+            .score("firefox")
+            .is_some()
+    };
+    assert!(matches("Fire", SearchMode::Fuzzy, false));
+    assert!(!matches("Fire", SearchMode::Fuzzy, true));
+    assert!(matches("^Fire", SearchMode::Regex, false));
+    assert!(!matches("^Fire", SearchMode::Regex, true));
+}
+
+#[test]
+fn match_ranges() {
+    let ranges = |term, mode, text| {
+        Matcher::new(term, mode, false)
+            .unwrap()
+            .match_ranges(text)
+            .into_iter()
+            .map(|range| (range.start, range.end))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ranges("fox", SearchMode::Substring, "FireFox"), [(4, 7)]);
+    assert_eq!(ranges("ff", SearchMode::Fuzzy, "FireFox"), [(0, 1), (4, 5)]);
+    assert_eq!(ranges("f.x", SearchMode::Regex, "FireFox"), [(4, 7)]);
+    assert!(ranges("x", SearchMode::Substring, "abc").is_empty());
+    // lowercasing changes the byte length of some characters
+    assert_eq!(ranges("b", SearchMode::Substring, "\u{130}b"), [(2, 3)]);
+}
+
+#[test]
+fn matcher_scope() {
+    let package = PackageDesc {
+        name: "firefox".into(),
+        description: Some("web browser".into()),
+        url: Some("https://mozilla.org".into()),
+        ..Default::default()
+    };
+    let matches = |term, scope| {
+        Matcher::new(term, SearchMode::Substring, false)
+            .unwrap()
+            .score_package(&package, scope)
+            .is_some()
+    };
+    assert!(matches("fire", SearchScope::Name));
+    assert!(!matches("browser", SearchScope::Name));
+    assert!(matches("browser", SearchScope::NameDescription));
+    assert!(!matches("mozilla", SearchScope::NameDescription));
+    assert!(matches("mozilla", SearchScope::All));
+}
+
+#[test]
+fn matcher_all_words() {
+    let package = PackageDesc {
+        name: "libgtk".into(),
+        description: Some("toolkit".into()),
+        ..Default::default()
+    };
+    let matches = |term, mode, scope| {
+        Matcher::new(term, mode, false)
+            .unwrap()
+            .score_package(&package, scope)
+            .is_some()
+    };
+    assert!(matches("lib gtk", SearchMode::Substring, SearchScope::Name));
+    assert!(matches(
+        " gtk  lib ",
+        SearchMode::Substring,
+        SearchScope::Name
+    ));
+    assert!(!matches("lib qt", SearchMode::Substring, SearchScope::Name));
+    assert!(matches("", SearchMode::Substring, SearchScope::Name));
+    assert!(matches("lbg tk", SearchMode::Fuzzy, SearchScope::Name));
+    // words can match different fields
+    assert!(!matches(
+        "gtk tool",
+        SearchMode::Substring,
+        SearchScope::Name
+    ));
+    assert!(matches(
+        "gtk tool",
+        SearchMode::Substring,
+        SearchScope::NameDescription
+    ));
+    // a regex is not split
+    assert!(!matches("lib gtk", SearchMode::Regex, SearchScope::Name));
+    let matcher = Matcher::new("tk gtk", SearchMode::Substring, false).unwrap();
+    let ranges = matcher.match_ranges("libgtk");
+    assert_eq!((ranges.len(), ranges[0].start, ranges[0].end), (1, 3, 6));
+}
+
+#[test]
+fn matcher_excluded_words() {
+    let matches = |term, mode, name: &str| {
+        let package = PackageDesc {
+            name: name.into(),
+            description: Some("development files".into()),
+            ..Default::default()
+        };
+        Matcher::new(term, mode, false)
+            .unwrap()
+            .score_package(&package, SearchScope::Name)
+            .is_some()
+    };
+    assert!(matches("python -dev", SearchMode::Substring, "python"));
+    assert!(!matches("python -dev", SearchMode::Substring, "python-dev"));
+    assert!(!matches("python !DEV", SearchMode::Substring, "python-dev"));
+    assert!(matches(
+        "python !qt -gtk",
+        SearchMode::Substring,
+        "python-dev"
+    ));
+    // only excluded words match everything else
+    assert!(matches("-qt", SearchMode::Substring, "python"));
+    assert!(!matches("-pyth", SearchMode::Fuzzy, "python"));
+    // the description isn't searched
+    assert!(matches("python -files", SearchMode::Substring, "python"));
+    // a lone dash is a word
+    assert!(!matches("python -", SearchMode::Substring, "python"));
+    // a regex is matched as a whole
+    assert!(matches("lib -dev", SearchMode::Regex, "lib -dev"));
+    let matcher = Matcher::new("py -dev", SearchMode::Substring, false).unwrap();
+    assert!(matcher.score("python").is_some());
+    assert!(matcher.score("python-dev").is_none());
+    assert_eq!(matcher.match_ranges("python").len(), 1);
+}
+
+#[test]
+fn matcher_regex() {
+    let matcher = Matcher::new("^lib.*-dev$", SearchMode::Regex, true).unwrap();
+    assert!(matcher.score("libfoo-dev").is_some());
+    assert!(matcher.score("foo-dev").is_none());
+    assert!(Matcher::new("(", SearchMode::Regex, true).is_err());
+}
@@ -0,0 +1,23 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+// `$var/leptohadron/file`, with `$HOME/home_fallback` instead of `$var` if it isn't set or is empty.
+// `None` if neither is set.
+pub fn path(var: &str, home_fallback: &str, file: &str) -> Option<PathBuf> {
+    let dir = match std::env::var_os(var).filter(|path| !path.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(home_fallback),
+    };
+    Some(dir.join("leptohadron").join(file))
+}
+
+// Reads the TOML file at `path` with `parse`. A missing file is not an error and gives the defaults.
+pub fn load_toml<T: Default>(path: &Path, parse: impl FnOnce(&str) -> Result<T>) -> Result<T> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(err) => return Err(err).context(format!("read {path:?}")),
+    };
+    parse(&contents).context(format!("parse {path:?}"))
+}
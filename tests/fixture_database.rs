@@ -0,0 +1,137 @@
+// Loads the synthetic database in `tests/fixtures/local` like the interface does.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use leptohadron::{dependencies, PackageBackend, PackageDesc, Pacman, Reason};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/local");
+
+fn load() -> BTreeMap<String, PackageDesc> {
+    leptohadron::from_directory(FIXTURE)
+        .unwrap()
+        .map(|package| {
+            let package = package.unwrap();
+            (package.name.clone(), package)
+        })
+        .collect()
+}
+
+#[test]
+fn fixture_packages() {
+    let packages = load();
+    let names: Vec<&str> = packages.keys().map(String::as_str).collect();
+    assert_eq!(
+        names,
+        [
+            "base",
+            "bash",
+            "cairo",
+            "firefox",
+            "glib2",
+            "gtk3",
+            "libnotify",
+            "pixman"
+        ]
+    );
+
+    let firefox = &packages["firefox"];
+    assert_eq!(firefox.version, "130.0-1");
+    assert_eq!(
+        firefox.url.as_deref(),
+        Some("https://www.mozilla.org/firefox/")
+    );
+    assert!(matches!(firefox.reason, Reason::Explicit));
+    assert_eq!(firefox.size, Some(254028267));
+    assert_eq!(firefox.install_date, Some(1725600000));
+    assert_eq!(firefox.dependencies[1].name, "nss");
+    assert_eq!(
+        firefox.dependencies[1].constraint.as_deref(),
+        Some(">=3.90")
+    );
+    let optional = &firefox.optional_dependencies[1];
+    assert_eq!(optional.name, "networkmanager");
+    assert_eq!(
+        optional.description.as_deref(),
+        Some("location detection via available WiFi networks: optional")
+    );
+
+    // without url and with an epoch in the version
+    let gtk3 = &packages["gtk3"];
+    assert_eq!(gtk3.url, None);
+    assert_eq!(gtk3.version, "1:3.24.43-4");
+    let dependencies: Vec<&str> = gtk3.dependencies.iter().map(|d| d.name.as_str()).collect();
+    assert_eq!(dependencies, ["glib2", "cairo", "sh"]);
+    assert_eq!(gtk3.groups, ["gnome"]);
+
+    // without reason, which means explicitly installed
+    assert!(matches!(packages["base"].reason, Reason::Explicit));
+    assert_eq!(packages["bash"].provides, ["sh"]);
+    assert!(packages.values().all(|package| package.warnings.is_empty()));
+}
+
+#[test]
+fn fixture_dependants() {
+    let packages = load();
+    let providers = dependencies::providers(&packages);
+    let dependants = dependencies::dependants(&packages, &providers);
+    let dependants_of = |name: &str| -> Vec<&str> {
+        dependants
+            .get(name)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect()
+    };
+    assert_eq!(dependants_of("glib2"), ["cairo", "gtk3", "libnotify"]);
+    // through what bash provides
+    assert_eq!(dependants_of("bash"), ["base", "gtk3"]);
+    // the optional dependency
+    assert_eq!(dependants_of("libnotify"), ["firefox"]);
+    // the cycle
+    assert_eq!(dependants_of("cairo"), ["gtk3", "pixman"]);
+    assert_eq!(dependants_of("pixman"), ["cairo"]);
+    assert!(dependants_of("firefox").is_empty());
+    // dependencies that aren't installed
+    let missing: BTreeSet<&str> = ["glibc", "networkmanager", "nss"].into();
+    assert!(missing.iter().all(|name| !dependants.contains_key(name)));
+}
+
+#[test]
+fn fixture_backend() {
+    let backend = Pacman {
+        dbpath: FIXTURE.to_string(),
+    };
+    assert_eq!(backend.packages().unwrap().count(), 8);
+    let packages = load();
+    assert_eq!(
+        backend.files(&packages["firefox"]).unwrap(),
+        ["usr/", "usr/bin/", "usr/bin/firefox"]
+    );
+    assert!(backend.files(&packages["bash"]).is_err());
+}
+
+#[test]
+fn fixture_compressed() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/compressed");
+    let packages: Vec<PackageDesc> = leptohadron::from_directory(path)
+        .unwrap()
+        .collect::<anyhow::Result<_>>()
+        .unwrap();
+    let mut names: Vec<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, ["vim", "zstd"]);
+    let zstd = packages.iter().find(|p| p.name == "zstd").unwrap();
+    assert_eq!(zstd.version, "1.5.6-1");
+    assert!(matches!(zstd.reason, Reason::Dependency));
+
+    // a damaged file is an error instead of a missing package
+    let damaged = std::env::temp_dir().join(format!("leptohadron-gzip-{}", std::process::id()));
+    std::fs::create_dir_all(damaged.join("a-1")).unwrap();
+    std::fs::write(damaged.join("a-1/desc.gz"), "not gzip").unwrap();
+    let results: Vec<_> = leptohadron::from_directory(damaged.to_str().unwrap())
+        .unwrap()
+        .collect();
+    std::fs::remove_dir_all(&damaged).unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].is_err());
+}